@@ -1,32 +1,172 @@
 use simple_grid::{Grid, GridIndex};
-use std::collections::{HashMap, HashSet};
+use std::{
+    collections::{HashMap, HashSet},
+    ops::{Add, Mul, Sub},
+};
 
 pub struct AntennaMap {
     antennas: Grid<Option<Antenna>>,
+    /// Cells that block the signal between antennas.
+    walls: Grid<bool>,
+    /// The antennas of each frequency.
+    frequencies: HashMap<char, Vec<GridIndex>>,
+    /// The rule that `antinode_counts` is kept up to date for.
+    rule: AntinodeRule,
+    /// How many antenna pairs create an antinode in each cell.
+    antinode_counts: Grid<u32>,
+    distinct_antinodes: usize,
 }
 
 impl AntennaMap {
     pub fn new(antennas: Grid<Option<Antenna>>) -> Self {
-        Self { antennas }
+        let walls = Grid::new_default(antennas.width(), antennas.height());
+        Self::with_walls(antennas, walls).unwrap()
     }
 
-    pub fn get_antinodes(&self) -> Grid<Vec<char>> {
-        let mut antinodes: Grid<Vec<char>> =
-            Grid::new_default(self.antennas.width(), self.antennas.height());
+    pub fn with_walls(
+        antennas: Grid<Option<Antenna>>,
+        walls: Grid<bool>,
+    ) -> Result<Self, &'static str> {
+        if antennas.dimensions() != walls.dimensions() {
+            return Err("walls must have the same dimensions as the antennas");
+        }
+        if antennas
+            .cell_iter()
+            .zip(walls.cell_iter())
+            .any(|(antenna, &wall)| antenna.is_some() && wall)
+        {
+            return Err("an antenna can't be placed on a wall");
+        }
 
-        let antennas = self.antennas_by_frequency();
-        for (frequency, antennas_with_frequency) in antennas {
-            for &a in &antennas_with_frequency {
-                for &b in &antennas_with_frequency {
-                    if a == b {
-                        continue;
-                    }
+        let mut frequencies: HashMap<char, Vec<GridIndex>> = HashMap::new();
+        for (idx, cell) in antennas.cells_with_indices_iter() {
+            if let Some(f) = cell {
+                frequencies.entry(f.frequency).or_default().push(idx);
+            }
+        }
 
-                    if let Some(antinode) = self.antinode_of(a, b) {
-                        antinodes[antinode].push(frequency);
-                    }
-                    if let Some(antinode) = self.antinode_of(b, a) {
-                        antinodes[antinode].push(frequency);
+        let mut map = Self {
+            antinode_counts: Grid::new_default(antennas.width(), antennas.height()),
+            antennas,
+            walls,
+            frequencies,
+            rule: AntinodeRule::default(),
+            distinct_antinodes: 0,
+        };
+        map.recount_antinodes();
+        Ok(map)
+    }
+
+    /// Places a wall, which recounts every antinode since any pair could be affected.
+    pub fn add_wall(&mut self, idx: GridIndex) -> Result<(), &'static str> {
+        match self.antennas.get(idx) {
+            None => return Err("wall is outside of the map"),
+            Some(Some(_)) => return Err("there is an antenna there"),
+            Some(None) => {}
+        }
+        self.walls[idx] = true;
+        self.recount_antinodes();
+
+        Ok(())
+    }
+
+    pub fn remove_wall(&mut self, idx: GridIndex) {
+        if let Some(wall) = self.walls.get_mut(idx) {
+            *wall = false;
+            self.recount_antinodes();
+        }
+    }
+
+    /// Whether no wall lies on the line between `a` and `b`, using the cells that Bresenham's
+    /// algorithm visits.
+    pub fn has_line_of_sight(&self, a: GridIndex, b: GridIndex) -> bool {
+        self.is_clear(a.into(), b.into())
+    }
+
+    /// Changes the rule used by [`AntennaMap::antinode_count`], which recounts every antinode.
+    pub fn set_antinode_rule(&mut self, rule: AntinodeRule) {
+        self.rule = rule;
+        self.recount_antinodes();
+    }
+
+    /// Places an antenna and updates the antinode counts for every pair it forms with antennas
+    /// of the same frequency.
+    pub fn add_antenna(&mut self, idx: GridIndex, antenna: Antenna) -> Result<(), &'static str> {
+        match self.antennas.get(idx) {
+            None => return Err("antenna is outside of the map"),
+            Some(Some(_)) => return Err("there is already an antenna there"),
+            Some(None) if self.walls[idx] => return Err("an antenna can't be placed on a wall"),
+            Some(None) => {}
+        }
+
+        let others = self
+            .frequencies
+            .get(&antenna.frequency)
+            .cloned()
+            .unwrap_or_default();
+        for other in others {
+            for antinode in self.antinodes_of(idx, other, &self.rule) {
+                self.antinode_counts[antinode] += 1;
+                if self.antinode_counts[antinode] == 1 {
+                    self.distinct_antinodes += 1;
+                }
+            }
+        }
+        self.frequencies
+            .entry(antenna.frequency)
+            .or_default()
+            .push(idx);
+        self.antennas[idx] = Some(antenna);
+
+        Ok(())
+    }
+
+    /// Removes the antenna at `idx`, if any, and takes back the antinodes it was part of.
+    pub fn remove_antenna(&mut self, idx: GridIndex) -> Option<Antenna> {
+        let antenna = self.antennas.get_mut(idx)?.take()?;
+
+        let others = self.frequencies.get_mut(&antenna.frequency).unwrap();
+        others.retain(|&other| other != idx);
+        for other in others.clone() {
+            for antinode in self.antinodes_of(idx, other, &self.rule) {
+                self.antinode_counts[antinode] -= 1;
+                if self.antinode_counts[antinode] == 0 {
+                    self.distinct_antinodes -= 1;
+                }
+            }
+        }
+
+        Some(antenna)
+    }
+
+    /// The number of cells with at least one antinode under the current rule.
+    pub fn antinode_count(&self) -> usize {
+        self.distinct_antinodes
+    }
+
+    fn recount_antinodes(&mut self) {
+        let antinodes = self.get_antinodes(&self.rule);
+        self.antinode_counts = Grid::new(
+            self.antennas.width(),
+            self.antennas.height(),
+            antinodes
+                .sources
+                .cell_iter()
+                .map(|sources| sources.len() as u32)
+                .collect(),
+        );
+        self.distinct_antinodes = antinodes.len();
+    }
+
+    pub fn get_antinodes(&self, rule: &AntinodeRule) -> Antinodes {
+        let mut antinodes = Antinodes::new(self.antennas.width(), self.antennas.height());
+
+        for (&frequency, antennas_with_frequency) in &self.frequencies {
+            for (i, &a) in antennas_with_frequency.iter().enumerate() {
+                for &b in &antennas_with_frequency[i + 1..] {
+                    let source = AntinodeSource::new(frequency, a, b);
+                    for antinode in self.antinodes_of(a, b, rule) {
+                        antinodes.add(antinode, source);
                     }
                 }
             }
@@ -35,23 +175,26 @@ impl AntennaMap {
         antinodes
     }
 
-    pub fn get_resonant_antinodes(&self) -> Grid<Vec<char>> {
-        let mut antinodes: Grid<Vec<char>> =
-            Grid::new_default(self.antennas.width(), self.antennas.height());
+    pub fn get_resonant_antinodes(&self, resonance: Resonance) -> Antinodes {
+        let mut antinodes = Antinodes::new(self.antennas.width(), self.antennas.height());
 
-        let antennas = self.antennas_by_frequency();
-        for (frequency, antennas_with_frequency) in antennas {
-            for &a in &antennas_with_frequency {
-                for &b in &antennas_with_frequency {
-                    if a == b {
+        for (&frequency, antennas_with_frequency) in &self.frequencies {
+            for (i, &a) in antennas_with_frequency.iter().enumerate() {
+                for &b in &antennas_with_frequency[i + 1..] {
+                    if !self.has_line_of_sight(a, b) {
                         continue;
                     }
-
-                    for resonant_antinode in self.resonant_antinodes_of(a, b) {
-                        antinodes[resonant_antinode].push(frequency);
-                    }
-                    for resonant_antinode in self.resonant_antinodes_of(b, a) {
-                        antinodes[resonant_antinode].push(frequency);
+                    let source = AntinodeSource::new(frequency, a, b);
+                    for ray in resonance.rays_of(a.into(), b.into(), self.bounds()) {
+                        let mut previous = None;
+                        for resonant_antinode in ray {
+                            // the signal stops at the first wall
+                            if previous.is_some_and(|p| !self.is_clear(p, resonant_antinode)) {
+                                break;
+                            }
+                            antinodes.add(self.within_bounds(resonant_antinode).unwrap(), source);
+                            previous = Some(resonant_antinode);
+                        }
                     }
                 }
             }
@@ -60,60 +203,497 @@ impl AntennaMap {
         antinodes
     }
 
-    fn antennas_by_frequency(&self) -> HashMap<char, HashSet<GridIndex>> {
-        let mut map: HashMap<char, HashSet<GridIndex>> = HashMap::new();
+    /// The same antennas, stored as a list of coordinates bounded by the size of this map.
+    pub fn to_sparse(&self) -> SparseAntennaMap {
+        SparseAntennaMap::new(
+            self.antennas
+                .cells_with_indices_iter()
+                .filter_map(|(idx, cell)| cell.as_ref().map(|a| (a.frequency, idx.into())))
+                .collect(),
+            self.bounds(),
+        )
+        .expect("a grid cell holds at most one antenna")
+    }
 
-        for (idx, cell) in self.antennas.cells_with_indices_iter() {
-            if let Some(f) = cell {
-                map.entry(f.frequency).or_default().insert(idx);
+    /// The antinodes of the pair that are inside the map and reachable from both antennas.
+    fn antinodes_of(&self, a: GridIndex, b: GridIndex, rule: &AntinodeRule) -> Vec<GridIndex> {
+        if !self.has_line_of_sight(a, b) {
+            return Vec::new();
+        }
+
+        rule.antinodes_of(a.into(), b.into())
+            .into_iter()
+            .filter(|&antinode| {
+                self.is_clear(a.into(), antinode) && self.is_clear(b.into(), antinode)
+            })
+            .filter_map(|antinode| self.within_bounds(antinode))
+            .collect()
+    }
+
+    /// Whether every cell on the line from `from` to `to`, inclusive, is inside the map and isn't
+    /// a wall.
+    fn is_clear(&self, from: Point, to: Point) -> bool {
+        bresenham(from, to)
+            .into_iter()
+            .all(|p| self.within_bounds(p).is_some_and(|idx| !self.walls[idx]))
+    }
+
+    fn bounds(&self) -> Bounds {
+        Bounds::Rect {
+            min: Point::new(0, 0),
+            max: Point::new(
+                self.antennas.width() as i64 - 1,
+                self.antennas.height() as i64 - 1,
+            ),
+        }
+    }
+
+    fn within_bounds(&self, point: Point) -> Option<GridIndex> {
+        within_bounds(&self.antennas, point)
+    }
+}
+
+fn within_bounds<T>(grid: &Grid<T>, point: Point) -> Option<GridIndex> {
+    if point.x < 0
+        || point.y < 0
+        || point.x >= grid.width() as i64
+        || point.y >= grid.height() as i64
+    {
+        None
+    } else {
+        Some(GridIndex::new(point.x as usize, point.y as usize))
+    }
+}
+
+/// An antenna map that only stores the antenna coordinates, so memory grows with the number of
+/// antennas rather than the area. The plane may be unbounded.
+pub struct SparseAntennaMap {
+    antennas: HashMap<char, Vec<Point>>,
+    bounds: Bounds,
+}
+
+impl SparseAntennaMap {
+    /// Fails if two antennas of the same frequency are at the same point, as that pair has no
+    /// direction to place antinodes along.
+    pub fn new(antennas: Vec<(char, Point)>, bounds: Bounds) -> Result<Self, &'static str> {
+        let mut map: HashMap<char, Vec<Point>> = HashMap::new();
+        for (frequency, point) in antennas {
+            let points = map.entry(frequency).or_default();
+            if points.contains(&point) {
+                return Err("two antennas of the same frequency share a point");
             }
+            points.push(point);
         }
 
-        map
+        Ok(Self {
+            antennas: map,
+            bounds,
+        })
     }
 
-    fn antinode_of(&self, from: GridIndex, to: GridIndex) -> Option<GridIndex> {
-        let x_dist = from.column() as isize - to.column() as isize;
-        let y_dist = from.row() as isize - to.row() as isize;
+    /// The antinodes within the bounds, along with the frequencies that created each of them.
+    pub fn get_antinodes(&self, rule: &AntinodeRule) -> HashMap<Point, HashSet<char>> {
+        let mut antinodes: HashMap<Point, HashSet<char>> = HashMap::new();
 
-        let antinode_column = from.column() as isize + x_dist;
-        let antinode_row = from.row() as isize + y_dist;
+        for (frequency, a, b) in self.pairs() {
+            for antinode in rule.antinodes_of(a, b) {
+                if self.bounds.contains(antinode) {
+                    antinodes.entry(antinode).or_default().insert(frequency);
+                }
+            }
+        }
 
-        self.within_bounds(antinode_column, antinode_row)
+        antinodes
+    }
+
+    /// The rays of resonant antinodes for every pair of antennas. On an unbounded plane the rays
+    /// never end, so they are only evaluated as far as they are consumed.
+    pub fn resonant_rays(
+        &self,
+        resonance: Resonance,
+    ) -> impl Iterator<Item = (char, Ray)> + use<'_> {
+        self.pairs().flat_map(move |(frequency, a, b)| {
+            resonance
+                .rays_of(a, b, self.bounds)
+                .into_iter()
+                .map(move |ray| (frequency, ray))
+        })
     }
 
-    fn resonant_antinodes_of(&self, from: GridIndex, to: GridIndex) -> Vec<GridIndex> {
-        let x_dist = from.column() as isize - to.column() as isize;
-        let y_dist = from.row() as isize - to.row() as isize;
+    fn pairs(&self) -> impl Iterator<Item = (char, Point, Point)> + use<'_> {
+        self.antennas.iter().flat_map(|(&frequency, points)| {
+            points
+                .iter()
+                .enumerate()
+                .flat_map(move |(i, &a)| points[i + 1..].iter().map(move |&b| (frequency, a, b)))
+        })
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct Point {
+    pub x: i64,
+    pub y: i64,
+}
+
+impl Point {
+    pub fn new(x: i64, y: i64) -> Self {
+        Self { x, y }
+    }
+
+    fn checked_add(self, rhs: Self) -> Option<Self> {
+        Some(Self::new(
+            self.x.checked_add(rhs.x)?,
+            self.y.checked_add(rhs.y)?,
+        ))
+    }
+
+    fn checked_mul(self, rhs: i64) -> Option<Self> {
+        Some(Self::new(
+            self.x.checked_mul(rhs)?,
+            self.y.checked_mul(rhs)?,
+        ))
+    }
+}
+
+impl From<GridIndex> for Point {
+    fn from(idx: GridIndex) -> Self {
+        Self::new(idx.column() as i64, idx.row() as i64)
+    }
+}
+
+impl Add for Point {
+    type Output = Point;
+
+    fn add(self, rhs: Self) -> Self::Output {
+        Self::new(self.x + rhs.x, self.y + rhs.y)
+    }
+}
 
-        let mut antinodes = Vec::new();
-        for step in 0.. {
-            let antinode_column = from.column() as isize + (step * x_dist);
-            let antinode_row = from.row() as isize + (step * y_dist);
+impl Sub for Point {
+    type Output = Point;
 
-            if let Some(antinode_idx) = self.within_bounds(antinode_column, antinode_row) {
-                antinodes.push(antinode_idx);
-            } else {
-                return antinodes;
+    fn sub(self, rhs: Self) -> Self::Output {
+        Self::new(self.x - rhs.x, self.y - rhs.y)
+    }
+}
+
+impl Mul<i64> for Point {
+    type Output = Point;
+
+    fn mul(self, rhs: i64) -> Self::Output {
+        Self::new(self.x * rhs, self.y * rhs)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Bounds {
+    Infinite,
+    /// Inclusive on both corners.
+    Rect {
+        min: Point,
+        max: Point,
+    },
+}
+
+impl Bounds {
+    pub fn contains(&self, point: Point) -> bool {
+        match self {
+            Bounds::Infinite => true,
+            Bounds::Rect { min, max } => {
+                (min.x..=max.x).contains(&point.x) && (min.y..=max.y).contains(&point.y)
             }
         }
+    }
+}
+
+/// The points `origin, origin + step, origin + 2 * step, ...`, until they leave the bounds.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Ray {
+    next: Option<Point>,
+    /// `None` for a ray that holds only its origin.
+    step: Option<Point>,
+    bounds: Bounds,
+}
+
+impl Ray {
+    fn new(origin: Point, step: Point, bounds: Bounds) -> Self {
+        Self {
+            next: Some(origin),
+            step: Some(step),
+            bounds,
+        }
+    }
 
-        unreachable!()
+    fn single(origin: Point, bounds: Bounds) -> Self {
+        Self {
+            next: Some(origin),
+            step: None,
+            bounds,
+        }
     }
+}
 
-    fn within_bounds(&self, column: isize, row: isize) -> Option<GridIndex> {
-        if column < 0
-            || row < 0
-            || column >= self.antennas.width() as isize
-            || row >= self.antennas.height() as isize
-        {
-            None
+impl Iterator for Ray {
+    type Item = Point;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let current = self.next.filter(|&p| self.bounds.contains(p))?;
+        self.next = self.step.and_then(|step| current.checked_add(step));
+        Some(current)
+    }
+}
+
+/// The antinodes of a map, along with the antenna pairs that created each of them.
+pub struct Antinodes {
+    sources: Grid<Vec<AntinodeSource>>,
+}
+
+impl Antinodes {
+    fn new(width: usize, height: usize) -> Self {
+        Self {
+            sources: Grid::new_default(width, height),
+        }
+    }
+
+    fn add(&mut self, antinode: GridIndex, source: AntinodeSource) {
+        let sources = &mut self.sources[antinode];
+        if !sources.contains(&source) {
+            sources.push(source);
+        }
+    }
+
+    /// The number of cells that contain an antinode.
+    pub fn len(&self) -> usize {
+        self.sources.cell_iter().filter(|s| !s.is_empty()).count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (GridIndex, &[AntinodeSource])> + use<'_> {
+        self.sources
+            .cells_with_indices_iter()
+            .filter(|(_, s)| !s.is_empty())
+            .map(|(idx, s)| (idx, s.as_slice()))
+    }
+
+    pub fn sources_at(&self, idx: GridIndex) -> &[AntinodeSource] {
+        self.sources.get(idx).map(|s| s.as_slice()).unwrap_or(&[])
+    }
+
+    pub fn frequencies_at(&self, idx: GridIndex) -> HashSet<char> {
+        self.sources_at(idx).iter().map(|s| s.frequency).collect()
+    }
+
+    /// The antinodes that the antenna at `antenna` is part of creating.
+    pub fn created_by(&self, antenna: GridIndex) -> impl Iterator<Item = GridIndex> + use<'_> {
+        self.iter()
+            .filter(move |(_, sources)| {
+                sources
+                    .iter()
+                    .any(|s| s.antennas.0 == antenna || s.antennas.1 == antenna)
+            })
+            .map(|(idx, _)| idx)
+    }
+
+    /// The antinodes where at least `min_frequencies` different frequencies interfere.
+    pub fn interference(
+        &self,
+        min_frequencies: usize,
+    ) -> impl Iterator<Item = GridIndex> + use<'_> {
+        self.iter()
+            .map(|(idx, _)| idx)
+            .filter(move |&idx| self.frequencies_at(idx).len() >= min_frequencies)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct AntinodeSource {
+    frequency: char,
+    antennas: (GridIndex, GridIndex),
+}
+
+impl AntinodeSource {
+    fn new(frequency: char, a: GridIndex, b: GridIndex) -> Self {
+        let antennas = if (a.row(), a.column()) <= (b.row(), b.column()) {
+            (a, b)
         } else {
-            Some(GridIndex::new(column as usize, row as usize))
+            (b, a)
+        };
+        Self {
+            frequency,
+            antennas,
+        }
+    }
+
+    pub fn frequency(&self) -> char {
+        self.frequency
+    }
+
+    /// The pair of antennas, in reading order.
+    pub fn antennas(&self) -> (GridIndex, GridIndex) {
+        self.antennas
+    }
+}
+
+/// Where antinodes appear relative to a pair of antennas: at the points on their line that are
+/// `near` parts from one antenna and `far` parts from the other.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct AntinodeRule {
+    near: i64,
+    far: i64,
+    /// Points outside the pair.
+    external: bool,
+    /// Points between the pair.
+    internal: bool,
+}
+
+impl AntinodeRule {
+    pub fn new(near: usize, far: usize, external: bool, internal: bool) -> Self {
+        assert!(near > 0 && far > 0, "ratio parts must be positive");
+        Self {
+            near: near as i64,
+            far: far as i64,
+            external,
+            internal,
+        }
+    }
+
+    /// The antinodes of the pair `a` and `b` that lie on integer coordinates. Antinodes beyond
+    /// the range of a `Point` are left out.
+    fn antinodes_of(&self, a: Point, b: Point) -> Vec<Point> {
+        // the antinode is at 'from + dist * near / divisor' along the line through the pair,
+        // worked out in i128 so that far apart pairs don't overflow
+        let (near, far) = (i128::from(self.near), i128::from(self.far));
+        let mut divisors = Vec::with_capacity(2);
+        if self.external && near != far {
+            divisors.push(near - far);
+        }
+        if self.internal {
+            divisors.push(near + far);
+        }
+
+        let mut antinodes = Vec::with_capacity(4);
+        for (from, to) in [(a, b), (b, a)] {
+            let antinode_coord = |from: i64, to: i64, divisor: i128| {
+                let offset = (i128::from(to) - i128::from(from)).checked_mul(near)?;
+                if offset % divisor != 0 {
+                    // not on a grid point
+                    return None;
+                }
+                i64::try_from(i128::from(from) + offset / divisor).ok()
+            };
+            for &divisor in &divisors {
+                let (Some(x), Some(y)) = (
+                    antinode_coord(from.x, to.x, divisor),
+                    antinode_coord(from.y, to.y, divisor),
+                ) else {
+                    continue;
+                };
+                let antinode = Point::new(x, y);
+                if !antinodes.contains(&antinode) {
+                    antinodes.push(antinode);
+                }
+            }
+        }
+
+        antinodes
+    }
+}
+
+impl Default for AntinodeRule {
+    /// Twice as far from one antenna as from the other, outside the pair.
+    fn default() -> Self {
+        Self::new(1, 2, true, false)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Resonance {
+    /// Steps away from each antenna by the full distance between the pair, as in the puzzle.
+    AntennaSpacing,
+    /// Every grid point exactly in line with the pair, in both directions. Differs from
+    /// `AntennaSpacing` when the pair's column and row distances share a common divisor.
+    EveryGridPoint,
+}
+
+impl Resonance {
+    /// The rays that together cover the resonant antinodes of the pair `a` and `b`.
+    fn rays_of(&self, a: Point, b: Point, bounds: Bounds) -> Vec<Ray> {
+        if a == b {
+            return Vec::new();
+        }
+
+        let (dx, dy) = (
+            i128::from(a.x) - i128::from(b.x),
+            i128::from(a.y) - i128::from(b.y),
+        );
+        let divisor = match self {
+            Resonance::AntennaSpacing => 1,
+            Resonance::EveryGridPoint => gcd(dx, dy),
+        };
+        let step = (i64::try_from(dx / divisor), i64::try_from(dy / divisor));
+        let (Ok(x), Ok(y)) = step else {
+            // a step this long leaves the range of a 'Point' from either antenna, so the
+            // antennas are the only antinodes
+            return vec![Ray::single(a, bounds), Ray::single(b, bounds)];
+        };
+        let step = Point::new(x, y);
+        let Some(back) = step.checked_mul(-1) else {
+            return vec![Ray::single(a, bounds), Ray::single(b, bounds)];
+        };
+
+        match self {
+            Resonance::AntennaSpacing => vec![Ray::new(a, step, bounds), Ray::new(b, back, bounds)],
+            // both rays start at 'a', so every ray starts from an antenna
+            Resonance::EveryGridPoint => vec![Ray::new(a, step, bounds), Ray::new(a, back, bounds)],
+        }
+    }
+}
+
+/// The cells on the line from `from` to `to`, both included. The line is always drawn in the
+/// same direction, so swapping the ends gives the same cells in reverse.
+fn bresenham(from: Point, to: Point) -> Vec<Point> {
+    if (to.x, to.y) < (from.x, from.y) {
+        let mut points = bresenham(to, from);
+        points.reverse();
+        return points;
+    }
+
+    let (dx, dy) = ((to.x - from.x).abs(), -(to.y - from.y).abs());
+    let step = Point::new((to.x - from.x).signum(), (to.y - from.y).signum());
+
+    let mut points = Vec::with_capacity((dx - dy) as usize + 1);
+    let mut current = from;
+    let mut error = dx + dy;
+    loop {
+        points.push(current);
+        if current == to {
+            return points;
+        }
+        let doubled_error = 2 * error;
+        if doubled_error >= dy {
+            error += dy;
+            current.x += step.x;
+        }
+        if doubled_error <= dx {
+            error += dx;
+            current.y += step.y;
         }
     }
 }
 
+fn gcd(a: i128, b: i128) -> i128 {
+    let (mut a, mut b) = (a.abs(), b.abs());
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    a
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
 pub struct Antenna {
     frequency: char,
 }
@@ -123,3 +703,399 @@ impl Antenna {
         Self { frequency }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn antenna_map(lines: &[&str]) -> AntennaMap {
+        let data = lines
+            .join("")
+            .chars()
+            .map(|c| match c {
+                '.' | '#' => None,
+                f => Some(Antenna::new(f)),
+            })
+            .collect();
+        AntennaMap::new(Grid::new(lines[0].len(), lines.len(), data))
+    }
+
+    #[test]
+    fn resonance_test() {
+        // the pair is two columns and two rows apart, so the points in between are skipped
+        // when stepping by the antenna spacing
+        let map = antenna_map(&[
+            "......", //
+            ".a....", //
+            "......", //
+            "...a..", //
+            "......", //
+            "......", //
+        ]);
+
+        assert_eq!(
+            map.get_resonant_antinodes(Resonance::AntennaSpacing).len(),
+            3
+        );
+        assert_eq!(
+            map.get_resonant_antinodes(Resonance::EveryGridPoint).len(),
+            6
+        );
+    }
+
+    #[test]
+    fn antinode_rule_test() {
+        let map = antenna_map(&[
+            "...........", //
+            "...........", //
+            "..a........", //
+            "...........", //
+            "...........", //
+            "...........", //
+            "......a....", //
+            "...........", //
+            "...........", //
+            "...........", //
+            "...........", //
+        ]);
+
+        let antinodes = map.get_antinodes(&AntinodeRule::default());
+        assert_eq!(
+            antinodes.frequencies_at(GridIndex::new(10, 10)),
+            HashSet::from(['a'])
+        );
+        assert_eq!(antinodes.len(), 1);
+
+        // a quarter of the way from each antenna
+        let antinodes = map.get_antinodes(&AntinodeRule::new(1, 3, false, true));
+        assert_eq!(
+            antinodes.frequencies_at(GridIndex::new(3, 3)),
+            HashSet::from(['a'])
+        );
+        assert_eq!(
+            antinodes.frequencies_at(GridIndex::new(5, 5)),
+            HashSet::from(['a'])
+        );
+        assert_eq!(antinodes.len(), 2);
+
+        // half the distance between the antennas outside of each one
+        let antinodes = map.get_antinodes(&AntinodeRule::new(1, 3, true, false));
+        assert_eq!(
+            antinodes.frequencies_at(GridIndex::new(0, 0)),
+            HashSet::from(['a'])
+        );
+        assert_eq!(
+            antinodes.frequencies_at(GridIndex::new(8, 8)),
+            HashSet::from(['a'])
+        );
+        assert_eq!(antinodes.len(), 2);
+
+        // there is no external point for equal parts
+        let antinodes = map.get_antinodes(&AntinodeRule::new(1, 1, true, true));
+        assert_eq!(
+            antinodes.sources_at(GridIndex::new(4, 4)),
+            &[AntinodeSource::new(
+                'a',
+                GridIndex::new(2, 2),
+                GridIndex::new(6, 6)
+            )]
+        );
+        assert_eq!(antinodes.len(), 1);
+
+        // a third of the way isn't on a grid point
+        assert_eq!(
+            map.get_antinodes(&AntinodeRule::new(1, 2, false, true))
+                .len(),
+            0
+        );
+    }
+
+    #[test]
+    fn provenance_test() {
+        let map = antenna_map(&[
+            "........", //
+            "........", //
+            "...ba...", //
+            "........", //
+            "...b.a..", //
+            "........", //
+            "........", //
+            "........", //
+        ]);
+
+        let antinodes = map.get_antinodes(&AntinodeRule::default());
+        assert_eq!(antinodes.len(), 3);
+
+        let interference: Vec<_> = antinodes.interference(2).collect();
+        assert_eq!(interference, vec![GridIndex::new(3, 0)]);
+        assert_eq!(
+            antinodes.frequencies_at(GridIndex::new(3, 0)),
+            HashSet::from(['a', 'b'])
+        );
+        let sources: HashSet<_> = antinodes
+            .sources_at(GridIndex::new(3, 0))
+            .iter()
+            .copied()
+            .collect();
+        assert_eq!(
+            sources,
+            HashSet::from([
+                AntinodeSource::new('a', GridIndex::new(4, 2), GridIndex::new(5, 4)),
+                AntinodeSource::new('b', GridIndex::new(3, 2), GridIndex::new(3, 4)),
+            ])
+        );
+
+        let created_by: Vec<_> = antinodes.created_by(GridIndex::new(5, 4)).collect();
+        assert_eq!(created_by, vec![GridIndex::new(3, 0), GridIndex::new(6, 6)]);
+    }
+
+    #[test]
+    fn sparse_test() {
+        let map = antenna_map(&[
+            "........", //
+            "........", //
+            "...ba...", //
+            "........", //
+            "...b.a..", //
+            "........", //
+            "........", //
+            "........", //
+        ]);
+        let sparse = map.to_sparse();
+
+        let antinodes = sparse.get_antinodes(&AntinodeRule::default());
+        assert_eq!(
+            antinodes.len(),
+            map.get_antinodes(&AntinodeRule::default()).len()
+        );
+        assert_eq!(antinodes[&Point::new(3, 0)], HashSet::from(['a', 'b']));
+
+        let resonant: HashSet<_> = sparse
+            .resonant_rays(Resonance::AntennaSpacing)
+            .flat_map(|(_, ray)| ray)
+            .collect();
+        assert_eq!(
+            resonant.len(),
+            map.get_resonant_antinodes(Resonance::AntennaSpacing).len()
+        );
+
+        let far_apart = SparseAntennaMap::new(
+            vec![
+                ('a', Point::new(0, 0)),
+                ('a', Point::new(2_000_000_000, 3_000_000_000)),
+            ],
+            Bounds::Infinite,
+        )
+        .unwrap();
+        let antinodes = far_apart.get_antinodes(&AntinodeRule::default());
+        assert!(antinodes.contains_key(&Point::new(-2_000_000_000, -3_000_000_000)));
+        assert!(antinodes.contains_key(&Point::new(4_000_000_000, 6_000_000_000)));
+
+        assert!(SparseAntennaMap::new(
+            vec![('a', Point::new(1, 1)), ('b', Point::new(1, 1))],
+            Bounds::Infinite
+        )
+        .is_ok());
+        assert!(SparseAntennaMap::new(
+            vec![('a', Point::new(1, 1)), ('a', Point::new(1, 1))],
+            Bounds::Infinite
+        )
+        .is_err());
+        for resonance in [Resonance::AntennaSpacing, Resonance::EveryGridPoint] {
+            assert!(resonance
+                .rays_of(Point::new(1, 1), Point::new(1, 1), Bounds::Infinite)
+                .is_empty());
+        }
+
+        let ray_points: HashSet<_> = far_apart
+            .resonant_rays(Resonance::EveryGridPoint)
+            .flat_map(|(_, ray)| ray.take(3))
+            .collect();
+        assert_eq!(
+            ray_points,
+            HashSet::from([
+                Point::new(0, 0),
+                Point::new(2, 3),
+                Point::new(4, 6),
+                Point::new(-2, -3),
+                Point::new(-4, -6),
+            ])
+        );
+    }
+
+    #[test]
+    fn incremental_test() {
+        let rule = AntinodeRule::new(1, 2, true, true);
+        let mut map = antenna_map(&[
+            "........", //
+            "........", //
+            "...b....", //
+            "........", //
+            "........", //
+            "........", //
+            "........", //
+            "........", //
+        ]);
+        map.set_antinode_rule(rule);
+        assert_eq!(map.antinode_count(), 0);
+
+        let placements = [
+            (GridIndex::new(4, 2), 'a'),
+            (GridIndex::new(5, 4), 'a'),
+            (GridIndex::new(3, 4), 'b'),
+            (GridIndex::new(2, 6), 'a'),
+            (GridIndex::new(6, 1), 'b'),
+        ];
+        for (idx, frequency) in placements {
+            map.add_antenna(idx, Antenna::new(frequency)).unwrap();
+            assert_eq!(map.antinode_count(), map.get_antinodes(&rule).len());
+        }
+        assert!(map
+            .add_antenna(GridIndex::new(4, 2), Antenna::new('c'))
+            .is_err());
+        assert!(map
+            .add_antenna(GridIndex::new(8, 2), Antenna::new('c'))
+            .is_err());
+
+        for (idx, frequency) in placements.into_iter().rev() {
+            assert_eq!(map.remove_antenna(idx), Some(Antenna::new(frequency)));
+            assert_eq!(map.antinode_count(), map.get_antinodes(&rule).len());
+        }
+        assert_eq!(map.remove_antenna(GridIndex::new(4, 2)), None);
+        assert_eq!(map.antinode_count(), 0);
+    }
+
+    #[test]
+    fn line_of_sight_symmetry_test() {
+        assert_eq!(
+            bresenham(Point::new(2, 1), Point::new(0, 0)),
+            bresenham(Point::new(0, 0), Point::new(2, 1))
+                .into_iter()
+                .rev()
+                .collect::<Vec<_>>()
+        );
+
+        let rule = AntinodeRule::default();
+        let mut walls = Grid::new_default(7, 7);
+        walls[GridIndex::new(1, 0)] = true;
+        let first = GridIndex::new(0, 0);
+        for second in walls.indices().filter(|&idx| idx != first && !walls[idx]) {
+            let mut map = AntennaMap::with_walls(Grid::new_default(7, 7), walls.clone()).unwrap();
+            map.add_antenna(first, Antenna::new('a')).unwrap();
+            map.add_antenna(second, Antenna::new('a')).unwrap();
+            assert_eq!(
+                map.antinode_count(),
+                map.get_antinodes(&rule).len(),
+                "{:?}",
+                second
+            );
+            assert_eq!(
+                map.has_line_of_sight(first, second),
+                map.has_line_of_sight(second, first)
+            );
+
+            map.remove_antenna(first);
+            assert_eq!(map.antinode_count(), 0);
+        }
+    }
+
+    #[test]
+    fn wall_test() {
+        let lines = [
+            "..........", //
+            "..a.......", //
+            "..........", //
+            "..a....b..", //
+            "..........", //
+            "..........", //
+            ".......b..", //
+            "..........", //
+            ".......#..", //
+            "..........", //
+        ];
+        let antennas = antenna_map(&lines).antennas;
+        let walls = Grid::new(
+            lines[0].len(),
+            lines.len(),
+            lines.join("").chars().map(|c| c == '#').collect(),
+        );
+        let mut map = AntennaMap::with_walls(antennas, walls).unwrap();
+
+        // the wall blocks the antinode of 'b' below the pair, but not the one above it
+        let antinodes = map.get_antinodes(&AntinodeRule::default());
+        assert_eq!(antinodes.len(), 2);
+        assert_eq!(
+            antinodes.frequencies_at(GridIndex::new(7, 0)),
+            HashSet::from(['b'])
+        );
+        assert_eq!(map.antinode_count(), 2);
+
+        // the resonant ray of 'b' stops right before the wall
+        let antinodes = map.get_resonant_antinodes(Resonance::EveryGridPoint);
+        let b_antinodes: Vec<_> = antinodes
+            .iter()
+            .filter(|(_, sources)| sources.iter().any(|s| s.frequency() == 'b'))
+            .map(|(idx, _)| idx.row())
+            .collect();
+        assert_eq!(b_antinodes, vec![0, 1, 2, 3, 4, 5, 6, 7]);
+
+        // a wall between the 'a' antennas stops them from interacting
+        assert!(map.has_line_of_sight(GridIndex::new(2, 1), GridIndex::new(2, 3)));
+        map.add_wall(GridIndex::new(2, 2)).unwrap();
+        assert!(!map.has_line_of_sight(GridIndex::new(2, 1), GridIndex::new(2, 3)));
+        assert_eq!(map.antinode_count(), 1);
+        assert!(map.add_wall(GridIndex::new(2, 3)).is_err());
+        assert!(map
+            .add_antenna(GridIndex::new(7, 8), Antenna::new('c'))
+            .is_err());
+
+        map.remove_wall(GridIndex::new(2, 2));
+        assert_eq!(map.antinode_count(), 2);
+    }
+
+    #[test]
+    fn overflow_test() {
+        let edge = SparseAntennaMap::new(
+            vec![
+                ('a', Point::new(i64::MAX - 1, 0)),
+                ('a', Point::new(-2, 0)),
+                ('b', Point::new(i64::MAX - 1, 1)),
+                ('b', Point::new(i64::MAX - 3, 1)),
+            ],
+            Bounds::Infinite,
+        )
+        .unwrap();
+        // only the antinode of 'b' that fits on the plane is left
+        let antinodes = edge.get_antinodes(&AntinodeRule::default());
+        assert_eq!(antinodes.len(), 1);
+        assert_eq!(
+            antinodes[&Point::new(i64::MAX - 5, 1)],
+            HashSet::from(['b'])
+        );
+
+        let resonant: HashSet<_> = edge
+            .resonant_rays(Resonance::AntennaSpacing)
+            .filter(|&(frequency, _)| frequency == 'a')
+            .flat_map(|(_, ray)| ray)
+            .collect();
+        assert_eq!(
+            resonant,
+            HashSet::from([Point::new(i64::MAX - 1, 0), Point::new(-2, 0)])
+        );
+
+        let every: HashSet<_> = edge
+            .resonant_rays(Resonance::EveryGridPoint)
+            .filter(|&(frequency, _)| frequency == 'a')
+            .flat_map(|(_, ray)| ray.take(3))
+            .collect();
+        assert_eq!(
+            every,
+            HashSet::from([
+                Point::new(i64::MAX - 3, 0),
+                Point::new(i64::MAX - 2, 0),
+                Point::new(i64::MAX - 1, 0),
+                Point::new(i64::MAX, 0),
+            ])
+        );
+    }
+}
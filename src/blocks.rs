@@ -1,12 +1,28 @@
-use std::{collections::HashMap, fmt::Display};
+use std::{
+    cmp::Reverse,
+    collections::{BTreeMap, BinaryHeap, HashMap},
+    fmt::Display,
+};
 
 type FileId = usize;
 
+#[derive(Clone)]
 pub struct FileBlocks {
     blocks: Vec<Block>,
+    next_id: FileId,
 }
 
 impl FileBlocks {
+    /// An empty disk with room for `disk_len` blocks.
+    pub fn new(disk_len: usize) -> Self {
+        let mut blocks = Vec::new();
+        if disk_len > 0 {
+            blocks.push(Block::new(disk_len, BlockKind::Empty));
+        }
+
+        Self { blocks, next_id: 0 }
+    }
+
     pub fn from_disk_map(disk_map: &[usize]) -> Self {
         let mut blocks = Vec::with_capacity(10 * disk_map.len());
         for (file_id, chunk) in disk_map.chunks(2).enumerate() {
@@ -20,114 +36,637 @@ impl FileBlocks {
             blocks.push(Block::new(empty_len, BlockKind::Empty));
         }
 
-        Self { blocks }
+        let next_id = disk_map.len().div_ceil(2);
+        Self { blocks, next_id }
+    }
+
+    /// Creates a file of `len` blocks in the leftmost free span that can hold it, or spread over
+    /// the free spans from the left if none can, and returns its id.
+    pub fn create(&mut self, len: usize) -> Result<FileId, &'static str> {
+        let file_id = self.next_id;
+        let kind = BlockKind::Used(file_id);
+        if len == 0 {
+            // an empty file takes no space, it only needs a place among the blocks
+            let at = self
+                .blocks
+                .iter()
+                .position(|b| b.is_empty())
+                .unwrap_or(self.blocks.len());
+            self.blocks.insert(at, Block::new(0, kind));
+        } else {
+            self.allocate(len, kind)?;
+        }
+        self.next_id += 1;
+
+        Ok(file_id)
+    }
+
+    /// Frees every block of the file, and forgets it even if it is empty.
+    pub fn delete(&mut self, file_id: FileId) -> Result<(), &'static str> {
+        let mut found = false;
+        for block in self
+            .blocks
+            .iter_mut()
+            .filter(|b| b.file_id() == Some(file_id))
+        {
+            block.kind = BlockKind::Empty;
+            found = true;
+        }
+        if !found {
+            return Err("no such file");
+        }
+        self.blocks = Self::merge_neighbors(std::mem::take(&mut self.blocks));
+
+        Ok(())
+    }
+
+    /// Adds `len` blocks to the end of the file, in place if the space after it is free and
+    /// in a new fragment otherwise.
+    pub fn grow(&mut self, file_id: FileId, len: usize) -> Result<(), &'static str> {
+        let Some(&last) = self.spans_of(file_id).last() else {
+            return Err("no such file");
+        };
+        if self.free_spans().map(|span| span.len).sum::<usize>() < len {
+            return Err("disk full");
+        }
+
+        let end = last.start + last.len;
+        let in_place = self
+            .free_spans()
+            .find(|span| span.start == end)
+            .map_or(0, |span| span.len.min(len));
+        let kind = BlockKind::Used(file_id);
+        self.fill(Span::new(end, in_place), kind);
+        if len == in_place {
+            return Ok(());
+        }
+
+        self.allocate(len - in_place, kind)
+    }
+
+    /// Moves every file into one contiguous span, keeping their order and packing them to the
+    /// start of the disk.
+    pub fn defragment(&mut self) {
+        let disk_len: usize = self.blocks.iter().map(|b| b.len).sum();
+        let mut files: Vec<Block> = Vec::new();
+        let mut seen: HashMap<BlockKind, usize> = HashMap::new();
+        for block in self.blocks.iter().filter(|b| b.is_used()) {
+            match seen.get(&block.kind) {
+                Some(&i) => files[i].len += block.len,
+                None => {
+                    seen.insert(block.kind, files.len());
+                    files.push(*block);
+                }
+            }
+        }
+
+        let used_len: usize = files.iter().map(|b| b.len).sum();
+        self.blocks = files;
+        if disk_len > used_len {
+            self.blocks
+                .push(Block::new(disk_len - used_len, BlockKind::Empty));
+        }
     }
 
     pub fn checksum(&self) -> usize {
         let mut sum = 0;
         let mut pos = 0;
         for &block in &self.blocks {
-            if block.is_used() {
-                let file_id = block.file_id().unwrap();
-                for _ in 0..block.len {
-                    sum += file_id * pos;
-                    pos += 1;
-                }
-            } else {
-                // move the pointer through the entire empty block
-                pos += block.len;
+            if let Some(file_id) = block.file_id() {
+                // file_id * (pos + (pos + 1) + ... + (pos + len - 1)), halving whichever of
+                // 'len' and 'len - 1' is even before multiplying so that long spans don't overflow
+                let (len, below) = (block.len, block.len.saturating_sub(1));
+                let triangle = if len % 2 == 0 {
+                    len / 2 * below
+                } else {
+                    len * (below / 2)
+                };
+                sum += file_id * (len * pos + triangle);
             }
+            pos += block.len;
         }
 
         sum
     }
 
+    /// Fills every free span from the left with blocks taken from the end of the disk, splitting
+    /// files where needed.
+    ///
+    /// One pointer walks the spans from the left and another takes file blocks from the right,
+    /// so the work is linear in the number of spans regardless of how long they are.
     pub fn compact_fragmented(&mut self) {
-        let Some(mut index_of_next_empty) = self.get_next_empty(0) else {
-            return;
-        };
+        self.compact_fragmented_with_events(|_| {});
+    }
 
-        let mut last_full = self.get_index_of_previous_used(self.blocks.len());
-        while let Some(mut index_of_used_block) = last_full {
-            if index_of_used_block < index_of_next_empty {
-                return;
-            }
-
-            let used_block = self.blocks[index_of_used_block];
-            let file_id = used_block.file_id().unwrap();
-            // remove the used block
-            self.blocks[index_of_used_block] = Block::new(used_block.len, BlockKind::Empty);
-            let mut remaining_len = used_block.len;
-            loop {
-                let empty_block = self.blocks[index_of_next_empty];
-                if remaining_len < empty_block.len {
-                    // use the first 'remaining_len' of the empty block for the used block
-                    self.blocks.insert(
-                        index_of_next_empty,
-                        Block::new(remaining_len, BlockKind::Used(file_id)),
-                    );
-                    self.blocks[index_of_next_empty + 1].len -= remaining_len;
-                    // need to shift the indices because we inserted something
-                    index_of_next_empty += 1;
-                    index_of_used_block += 1;
-                    break;
-                } else if remaining_len == empty_block.len {
-                    self.blocks[index_of_next_empty] =
-                        Block::new(remaining_len, BlockKind::Used(file_id));
-                    index_of_next_empty = self.get_next_empty(index_of_next_empty).unwrap();
+    /// [`Self::compact_fragmented`], calling `on_move` for every piece of a file it moves.
+    pub fn compact_fragmented_with_events(&mut self, mut on_move: impl FnMut(MoveEvent)) {
+        let disk_len: usize = self.blocks.iter().map(|b| b.len).sum();
+        let mut blocks = std::mem::take(&mut self.blocks);
+        let mut compacted = Vec::with_capacity(blocks.len() + 1);
+        let starts: Vec<usize> = blocks
+            .iter()
+            .scan(0, |pos, block| {
+                let start = *pos;
+                *pos += block.len;
+                Some(start)
+            })
+            .collect();
+        let mut compacted_len = 0;
+
+        let (mut left, mut right) = (0, blocks.len());
+        while left < right {
+            let block = blocks[left];
+            left += 1;
+            if block.is_used() {
+                compacted.push(block);
+                compacted_len += block.len;
+                continue;
+            }
+
+            let mut space = block.len;
+            while space > 0 {
+                // skip the free spans at the end, they have nothing to move
+                while right > left && blocks[right - 1].is_empty() {
+                    right -= 1;
+                }
+                if right <= left {
                     break;
-                } else {
-                    // remaining_len > empty_block.len
-                    self.blocks[index_of_next_empty] =
-                        Block::new(empty_block.len, BlockKind::Used(file_id));
-                    remaining_len -= empty_block.len;
-                    index_of_next_empty = self.get_next_empty(index_of_next_empty).unwrap();
+                }
+
+                let last = &mut blocks[right - 1];
+                let moved = last.len.min(space);
+                if let (true, Some(file_id)) = (moved > 0, last.file_id()) {
+                    compacted.push(Block::new(moved, last.kind));
+                    on_move(MoveEvent {
+                        file_id,
+                        from: starts[right - 1] + last.len - moved,
+                        to: compacted_len,
+                        len: moved,
+                    });
+                    compacted_len += moved;
+                }
+                last.len -= moved;
+                space -= moved;
+                if last.len == 0 {
+                    right -= 1;
                 }
             }
-            last_full = self.get_index_of_previous_used(index_of_used_block);
         }
+
+        let used_len: usize = compacted.iter().map(|b| b.len).sum();
+        if disk_len > used_len {
+            compacted.push(Block::new(disk_len - used_len, BlockKind::Empty));
+        }
+        self.blocks = compacted;
     }
 
+    /// Moves every file, from the last one to the first, into the leftmost free span that can
+    /// hold all of it, if there is one to the left of the file.
+    ///
+    /// The starts of the free spans are kept in one min-heap for each span length on the disk,
+    /// so the leftmost span that fits is the smallest of the heap tops for all lengths that
+    /// are large enough. Finding it looks at every such top, so each file costs time linear in
+    /// the number of distinct free span lengths. Distinct lengths add up to at most the disk
+    /// length, so there are fewer than `sqrt(2 * disk_len)` of them, and the whole pass takes
+    /// `O(files * sqrt(disk_len))` in the worst case. Puzzle inputs only have lengths up to 9.
     pub fn compact_whole(&mut self) {
-        let mut last_used_index = self.blocks.len();
-        while let Some(mut used_block_idx) = self.get_index_of_previous_used(last_used_index) {
-            last_used_index = used_block_idx;
-            let used_block = self.blocks[used_block_idx];
-            let Some(mut empty_block_idx) = self.get_next_empty(0) else {
-                return;
+        self.compact_whole_with_events(|_| {});
+    }
+
+    /// [`Self::compact_whole`], calling `on_move` for every file it moves.
+    pub fn compact_whole_with_events(&mut self, mut on_move: impl FnMut(MoveEvent)) {
+        let mut files = Vec::new();
+        let mut free_spans: BTreeMap<usize, BinaryHeap<Reverse<usize>>> = BTreeMap::new();
+
+        let mut pos = 0;
+        for block in &self.blocks {
+            match block.kind {
+                BlockKind::Used(_) => files.push((pos, *block)),
+                BlockKind::Empty if block.len > 0 => {
+                    free_spans.entry(block.len).or_default().push(Reverse(pos))
+                }
+                BlockKind::Empty => {}
+            }
+            pos += block.len;
+        }
+        let disk_len = pos;
+
+        for (file_pos, file) in files.iter_mut().rev() {
+            let leftmost_fit = free_spans
+                .range(file.len.max(1)..)
+                .filter_map(|(&len, starts)| starts.peek().map(|&Reverse(start)| (start, len)))
+                .min();
+            let Some((start, len)) = leftmost_fit else {
+                continue;
             };
-            loop {
-                // go through all empty blocks to the left of the used block
-                if empty_block_idx > used_block_idx {
-                    break;
+            if start > *file_pos {
+                continue;
+            }
+
+            if let Some(starts) = free_spans.get_mut(&len) {
+                starts.pop();
+                if starts.is_empty() {
+                    free_spans.remove(&len);
                 }
-                let empty_block = self.blocks[empty_block_idx];
-                if empty_block.len == used_block.len {
-                    // the used block fits exactly, just swap
-                    self.blocks.swap(used_block_idx, empty_block_idx);
-                    break;
-                } else if empty_block.len > used_block.len {
-                    // the used block fits, but some space remains in the empty block
-                    self.blocks[used_block_idx] = Block::new(used_block.len, BlockKind::Empty);
-                    self.blocks.insert(empty_block_idx, used_block);
-                    self.blocks[empty_block_idx + 1].len -= used_block.len;
+            }
+            if len > file.len {
+                free_spans
+                    .entry(len - file.len)
+                    .or_default()
+                    .push(Reverse(start + file.len));
+            }
+            // the space the file leaves behind is to the right of every file that is left to
+            // move, so it never needs to be added to the heaps
+            if let Some(file_id) = file.file_id() {
+                on_move(MoveEvent {
+                    file_id,
+                    from: *file_pos,
+                    to: start,
+                    len: file.len,
+                });
+            }
+            *file_pos = start;
+        }
 
-                    last_used_index += 1; // adjust index due to insertion
-                    break;
-                } else {
-                    // need to look for another empty block
-                    empty_block_idx = self.get_next_empty(empty_block_idx).unwrap();
+        files.sort_by_key(|&(file_pos, _)| file_pos);
+        self.blocks = Vec::with_capacity(2 * files.len());
+        let mut pos = 0;
+        for (file_pos, file) in files {
+            if file_pos > pos {
+                self.blocks
+                    .push(Block::new(file_pos - pos, BlockKind::Empty));
+            }
+            self.blocks.push(file);
+            pos = file_pos + file.len;
+        }
+        if disk_len > pos {
+            self.blocks
+                .push(Block::new(disk_len - pos, BlockKind::Empty));
+        }
+    }
+
+    /// Moves the blocks described by `event`, which must hold the file and land on free space.
+    pub fn apply(&mut self, event: &MoveEvent) -> Result<(), &'static str> {
+        let (from, to) = (
+            Span::new(event.from, event.len),
+            Span::new(event.to, event.len),
+        );
+        let kind = BlockKind::Used(event.file_id);
+        if !self.is_all(from, kind) {
+            return Err("source is not the file");
+        }
+        if !self.is_all(to, BlockKind::Empty) {
+            return Err("destination is not free");
+        }
+        self.move_span(from, event.to, kind);
+
+        Ok(())
+    }
+
+    /// The layouts after each of `events` in turn, starting from this one.
+    pub fn replay<'a>(
+        &self,
+        events: &'a [MoveEvent],
+    ) -> impl Iterator<Item = Result<FileBlocks, &'static str>> + 'a {
+        let mut layout = self.clone();
+        events.iter().map(move |event| {
+            layout.apply(event)?;
+            Ok(layout.clone())
+        })
+    }
+
+    /// Moves every file, from the last one to the first, into the free span to its left that
+    /// `strategy` picks, and reports what the compaction did.
+    pub fn compact_with(&mut self, strategy: &mut dyn CompactionStrategy) -> CompactionReport {
+        let mut files = Vec::new();
+        let mut pos = 0;
+        for block in &self.blocks {
+            if block.is_used() {
+                files.push((Span::new(pos, block.len), block.kind));
+            }
+            pos += block.len;
+        }
+
+        let mut report = CompactionReport::default();
+        for &(file, kind) in files.iter().rev() {
+            if file.len == 0 {
+                continue;
+            }
+            let candidates: Vec<Span> = self
+                .free_spans()
+                .take_while(|span| span.start < file.start)
+                .filter(|span| span.len >= file.len)
+                .collect();
+            if candidates.is_empty() {
+                continue;
+            }
+            let Some(target) = strategy.choose(self, file, &candidates) else {
+                continue;
+            };
+
+            self.move_span(file, target.start, kind);
+            report.files_moved += 1;
+            report.blocks_copied += file.len;
+        }
+
+        report.fragmentation = self.fragmentation();
+        report
+    }
+
+    /// External fragmentation of the free space: `0.0` when it is all in one span, approaching
+    /// `1.0` as it is scattered over many small spans.
+    pub fn fragmentation(&self) -> f64 {
+        let (largest, total) = self.free_spans().fold((0, 0), |(largest, total), span| {
+            (largest.max(span.len), total + span.len)
+        });
+        if total == 0 {
+            return 0.0;
+        }
+
+        1.0 - largest as f64 / total as f64
+    }
+
+    /// Measures how scattered the files and the free space are.
+    pub fn stats(&self) -> LayoutStats {
+        let mut fragments = HashMap::new();
+        // where the last fragment of each file ended, and the gaps between them
+        let mut seeks: HashMap<FileId, (usize, usize)> = HashMap::new();
+
+        let mut pos = 0;
+        let mut previous = None;
+        for block in self.blocks.iter().filter(|b| b.len > 0) {
+            match block.file_id() {
+                Some(file_id) if previous == Some(block.kind) => {
+                    seeks
+                        .entry(file_id)
+                        .and_modify(|(end, _)| *end += block.len);
+                }
+                Some(file_id) => {
+                    *fragments.entry(file_id).or_insert(0) += 1;
+                    let (end, distance) = seeks.entry(file_id).or_insert((pos, 0));
+                    *distance += pos - *end;
+                    *end = pos + block.len;
                 }
+                None => {}
+            }
+            previous = Some(block.kind);
+            pos += block.len;
+        }
+
+        let total_seek: usize = seeks.values().map(|&(_, distance)| distance).sum();
+        let (largest_free_span, total_free) =
+            self.free_spans().fold((0, 0), |(largest, total), span| {
+                (largest.max(span.len), total + span.len)
+            });
+        LayoutStats {
+            average_seek_distance: if seeks.is_empty() {
+                0.0
+            } else {
+                total_seek as f64 / seeks.len() as f64
+            },
+            fragments,
+            largest_free_span,
+            total_free,
+            fragmentation: self.fragmentation(),
+        }
+    }
+
+    /// The free spans on the disk, from left to right. Free blocks with only empty files
+    /// between them are one span.
+    fn free_spans(&self) -> impl Iterator<Item = Span> + '_ {
+        let mut blocks = self.blocks.iter().filter(|b| b.len > 0).peekable();
+        let mut pos = 0;
+        std::iter::from_fn(move || loop {
+            let block = blocks.next()?;
+            let start = pos;
+            pos += block.len;
+            if block.is_used() {
+                continue;
+            }
+
+            let mut len = block.len;
+            while let Some(next) = blocks.next_if(|b| b.is_empty()) {
+                len += next.len;
+                pos += next.len;
+            }
+            return Some(Span::new(start, len));
+        })
+    }
+
+    /// The fragments of a file, from left to right.
+    fn spans_of(&self, file_id: FileId) -> Vec<Span> {
+        let mut spans = Vec::new();
+        let mut pos = 0;
+        for block in &self.blocks {
+            if block.file_id() == Some(file_id) {
+                spans.push(Span::new(pos, block.len));
             }
+            pos += block.len;
+        }
+
+        spans
+    }
+
+    /// Gives `len` free blocks to `kind`, in the leftmost span that can hold all of them or
+    /// else in as many spans from the left as it takes.
+    fn allocate(&mut self, len: usize, kind: BlockKind) -> Result<(), &'static str> {
+        let fit = self.free_spans().find(|span| span.len >= len);
+        if let Some(span) = fit {
+            self.fill(Span::new(span.start, len), kind);
+            return Ok(());
+        }
+
+        let mut remaining = len;
+        let mut spans = Vec::new();
+        for span in self.free_spans() {
+            let taken = span.len.min(remaining);
+            spans.push(Span::new(span.start, taken));
+            remaining -= taken;
+            if remaining == 0 {
+                break;
+            }
+        }
+        if remaining > 0 {
+            return Err("disk full");
+        }
+        for span in spans {
+            self.fill(span, kind);
         }
+
+        Ok(())
     }
 
-    fn get_next_empty(&self, start: usize) -> Option<usize> {
-        (start + 1..self.blocks.len()).find(|&i| self.blocks[i].is_empty())
+    /// Whether every block in `span` is of `kind`.
+    fn is_all(&self, span: Span, kind: BlockKind) -> bool {
+        let end = span.start + span.len;
+        let mut pos = 0;
+        self.blocks.iter().all(|block| {
+            let (block_start, block_end) = (pos, pos + block.len);
+            pos = block_end;
+            block_end <= span.start || block_start >= end || block.kind == kind
+        }) && end <= pos
     }
 
-    fn get_index_of_previous_used(&self, start: usize) -> Option<usize> {
-        (0..start).rev().find(|&i| self.blocks[i].is_used())
+    /// Moves the blocks of `from` to `to`, freeing the space they occupied.
+    fn move_span(&mut self, from: Span, to: usize, kind: BlockKind) {
+        self.fill(from, BlockKind::Empty);
+        self.fill(Span::new(to, from.len), kind);
+    }
+
+    /// Overwrites the blocks in `span` with `kind`, splitting the spans it cuts through and
+    /// merging neighbours of the same kind.
+    fn fill(&mut self, span: Span, kind: BlockKind) {
+        if span.len == 0 {
+            return;
+        }
+
+        let end = span.start + span.len;
+        let mut blocks = Vec::with_capacity(self.blocks.len() + 2);
+        let mut filled = false;
+
+        let mut pos = 0;
+        for &block in &self.blocks {
+            let (block_start, block_end) = (pos, pos + block.len);
+            pos = block_end;
+            if block_end <= span.start {
+                blocks.push(block);
+                continue;
+            }
+            if block_start >= end {
+                if !filled {
+                    blocks.push(Block::new(span.len, kind));
+                    filled = true;
+                }
+                blocks.push(block);
+                continue;
+            }
+
+            if block_start < span.start {
+                blocks.push(Block::new(span.start - block_start, block.kind));
+            }
+            if !filled {
+                blocks.push(Block::new(span.len, kind));
+                filled = true;
+            }
+            if block_end > end {
+                blocks.push(Block::new(block_end - end, block.kind));
+            }
+        }
+        if !filled {
+            blocks.push(Block::new(span.len, kind));
+        }
+
+        self.blocks = Self::merge_neighbors(blocks);
+    }
+
+    /// Merges neighboring blocks of the same kind and drops empty free blocks.
+    fn merge_neighbors(blocks: Vec<Block>) -> Vec<Block> {
+        let mut merged: Vec<Block> = Vec::with_capacity(blocks.len());
+        for block in blocks {
+            match merged.last_mut() {
+                _ if block.is_empty() && block.len == 0 => {}
+                Some(last) if last.kind == block.kind => last.len += block.len,
+                _ => merged.push(block),
+            }
+        }
+
+        merged
+    }
+
+    /// Writes the layout with one character or token per block, and `.` for free blocks.
+    pub fn render<'a>(&'a self, rendering: Rendering<'a>) -> Rendered<'a> {
+        Rendered {
+            file_blocks: self,
+            rendering,
+        }
+    }
+
+    /// The layout in the dense disk-map format, with files numbered again from left to right.
+    ///
+    /// Neighbouring blocks of the same file are written as one span. Spans longer than 9 blocks
+    /// are split, so a long file becomes several files in a row and a long free span is broken
+    /// up by empty files.
+    pub fn to_disk_map(&self) -> Vec<usize> {
+        let mut runs: Vec<Block> = Vec::with_capacity(self.blocks.len());
+        for &block in self.blocks.iter().filter(|b| b.len > 0) {
+            match runs.last_mut() {
+                Some(last) if last.kind == block.kind => last.len += block.len,
+                _ => runs.push(block),
+            }
+        }
+
+        let mut disk_map = Vec::with_capacity(2 * runs.len());
+        for block in runs {
+            let mut remaining = block.len;
+            while remaining > 0 {
+                // even entries are files and odd ones free spans
+                if (disk_map.len() % 2 == 0) != block.is_used() {
+                    disk_map.push(0);
+                }
+                let len = remaining.min(9);
+                disk_map.push(len);
+                remaining -= len;
+            }
+        }
+
+        disk_map
+    }
+
+    /// The runs of blocks that differ between this layout and `other`. Blocks past the end of
+    /// the shorter disk count as free.
+    pub fn diff(&self, other: &FileBlocks) -> Vec<LayoutChange> {
+        let mut changes: Vec<LayoutChange> = Vec::new();
+        let (mut before, mut after) = (self.blocks.iter(), other.blocks.iter());
+        let (mut current_before, mut current_after) = (
+            Block::new(0, BlockKind::Empty),
+            Block::new(0, BlockKind::Empty),
+        );
+
+        let mut pos = 0;
+        loop {
+            while current_before.len == 0 {
+                let Some(&block) = before.next() else {
+                    current_before.kind = BlockKind::Empty;
+                    break;
+                };
+                current_before = block;
+            }
+            while current_after.len == 0 {
+                let Some(&block) = after.next() else {
+                    current_after.kind = BlockKind::Empty;
+                    break;
+                };
+                current_after = block;
+            }
+
+            let len = match (current_before.len, current_after.len) {
+                (0, 0) => break,
+                (0, len) | (len, 0) => len,
+                (before_len, after_len) => before_len.min(after_len),
+            };
+            let (old, new) = (current_before.file_id(), current_after.file_id());
+            if old != new {
+                match changes.last_mut() {
+                    Some(change)
+                        if change.span.start + change.span.len == pos
+                            && (change.before, change.after) == (old, new) =>
+                    {
+                        change.span.len += len
+                    }
+                    _ => changes.push(LayoutChange {
+                        span: Span::new(pos, len),
+                        before: old,
+                        after: new,
+                    }),
+                }
+            }
+
+            current_before.len = current_before.len.saturating_sub(len);
+            current_after.len = current_after.len.saturating_sub(len);
+            pos += len;
+        }
+
+        changes
     }
 
     pub fn get_string_if_possible(&self) -> Option<String> {
@@ -156,6 +695,172 @@ impl FileBlocks {
     }
 }
 
+/// How [`FileBlocks::render`] writes file ids.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rendering<'a> {
+    /// File `id` is the `id`th character of the alphabet. Ids past its end are bracketed.
+    Alphabet(&'a str),
+    /// Every block is its id in brackets, like `[12]`.
+    Bracketed,
+}
+
+pub struct Rendered<'a> {
+    file_blocks: &'a FileBlocks,
+    rendering: Rendering<'a>,
+}
+
+impl Display for Rendered<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let alphabet: Vec<char> = match self.rendering {
+            Rendering::Alphabet(alphabet) => alphabet.chars().collect(),
+            Rendering::Bracketed => Vec::new(),
+        };
+        for block in &self.file_blocks.blocks {
+            for _ in 0..block.len {
+                match block.file_id() {
+                    None => write!(f, ".")?,
+                    Some(id) if id < alphabet.len() => write!(f, "{}", alphabet[id])?,
+                    Some(id) => write!(f, "[{}]", id)?,
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Renders file ids as digits, bracketing those of 10 and above.
+impl Display for FileBlocks {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.render(Rendering::Alphabet("0123456789")).fmt(f)
+    }
+}
+
+/// A run of blocks that belongs to `before` in one layout and to `after` in another, with
+/// `None` for free blocks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LayoutChange {
+    pub span: Span,
+    pub before: Option<FileId>,
+    pub after: Option<FileId>,
+}
+
+/// `len` blocks of a file moved from `from` to `to` during compaction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MoveEvent {
+    pub file_id: FileId,
+    pub from: usize,
+    pub to: usize,
+    pub len: usize,
+}
+
+/// A run of consecutive blocks on the disk.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Span {
+    pub start: usize,
+    pub len: usize,
+}
+
+impl Span {
+    pub fn new(start: usize, len: usize) -> Self {
+        Self { start, len }
+    }
+}
+
+/// See [`FileBlocks::stats`].
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct LayoutStats {
+    /// The number of separate runs of blocks each file is split into.
+    pub fragments: HashMap<FileId, usize>,
+    pub largest_free_span: usize,
+    pub total_free: usize,
+    /// See [`FileBlocks::fragmentation`].
+    pub fragmentation: f64,
+    /// The blocks skipped while reading a file's fragments from left to right, averaged over
+    /// all files.
+    pub average_seek_distance: f64,
+}
+
+/// What [`FileBlocks::compact_with`] did to the disk.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct CompactionReport {
+    pub files_moved: usize,
+    pub blocks_copied: usize,
+    /// See [`FileBlocks::fragmentation`].
+    pub fragmentation: f64,
+}
+
+/// Decides where files go during [`FileBlocks::compact_with`].
+pub trait CompactionStrategy {
+    /// Picks the span to move `file` into from `candidates`, the free spans to the left of it
+    /// that are large enough, sorted by start. Returning `None` leaves the file where it is.
+    fn choose(&mut self, blocks: &FileBlocks, file: Span, candidates: &[Span]) -> Option<Span>;
+}
+
+/// Takes the leftmost span, which is what [`FileBlocks::compact_whole`] does.
+pub struct FirstFit;
+
+impl CompactionStrategy for FirstFit {
+    fn choose(&mut self, _: &FileBlocks, _: Span, candidates: &[Span]) -> Option<Span> {
+        candidates.first().copied()
+    }
+}
+
+/// Takes the smallest span, the leftmost one on ties.
+pub struct BestFit;
+
+impl CompactionStrategy for BestFit {
+    fn choose(&mut self, _: &FileBlocks, _: Span, candidates: &[Span]) -> Option<Span> {
+        candidates.iter().min_by_key(|span| span.len).copied()
+    }
+}
+
+/// Takes the largest span, the leftmost one on ties.
+pub struct WorstFit;
+
+impl CompactionStrategy for WorstFit {
+    fn choose(&mut self, _: &FileBlocks, _: Span, candidates: &[Span]) -> Option<Span> {
+        candidates
+            .iter()
+            .min_by_key(|span| Reverse(span.len))
+            .copied()
+    }
+}
+
+/// Takes the first span at or after where the previous file went, wrapping around to the
+/// leftmost one.
+#[derive(Default)]
+pub struct NextFit {
+    last: usize,
+}
+
+impl CompactionStrategy for NextFit {
+    fn choose(&mut self, _: &FileBlocks, _: Span, candidates: &[Span]) -> Option<Span> {
+        let span = candidates
+            .iter()
+            .find(|span| span.start >= self.last)
+            .or(candidates.first())
+            .copied()?;
+        self.last = span.start;
+        Some(span)
+    }
+}
+
+/// Takes the leftmost span, but only moves the file if that lowers the fragmentation of the
+/// free space.
+pub struct ReducesFragmentation;
+
+impl CompactionStrategy for ReducesFragmentation {
+    fn choose(&mut self, blocks: &FileBlocks, file: Span, candidates: &[Span]) -> Option<Span> {
+        let span = *candidates.first()?;
+        // only the free spans count towards fragmentation, so which file moves doesn't matter
+        let mut moved = blocks.clone();
+        moved.move_span(file, span.start, BlockKind::Used(0));
+
+        (moved.fragmentation() < blocks.fragmentation()).then_some(span)
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 struct Block {
     len: usize,
@@ -185,8 +890,328 @@ impl Block {
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 enum BlockKind {
     Used(FileId),
     Empty,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXAMPLE: [usize; 19] = [2, 3, 3, 3, 1, 3, 3, 1, 2, 1, 4, 1, 4, 1, 3, 1, 4, 0, 2];
+
+    #[test]
+    fn compact_whole_test() {
+        let mut fb = FileBlocks::from_disk_map(&EXAMPLE);
+        fb.compact_whole();
+
+        assert_eq!(
+            fb.get_string_if_possible().unwrap(),
+            "00992111777.44.333....5555.6666.....8888.."
+        );
+        assert_eq!(fb.checksum(), 2858);
+    }
+
+    #[test]
+    fn compact_with_test() {
+        let mut fb = FileBlocks::from_disk_map(&EXAMPLE);
+        let report = fb.compact_with(&mut FirstFit);
+        assert_eq!(
+            fb.get_string_if_possible().unwrap(),
+            "00992111777.44.333....5555.6666.....8888.."
+        );
+        assert_eq!(fb.checksum(), 2858);
+        assert_eq!((report.files_moved, report.blocks_copied), (4, 8));
+        assert_eq!(report.fragmentation, fb.fragmentation());
+    }
+
+    #[test]
+    fn strategies_test() {
+        // 0....1..2.33
+        let disk_map = [1, 4, 1, 2, 1, 1, 2];
+        let layout = |strategy: &mut dyn CompactionStrategy| {
+            let mut fb = FileBlocks::from_disk_map(&disk_map);
+            let report = fb.compact_with(strategy);
+            (fb.get_string_if_possible().unwrap(), report.files_moved)
+        };
+
+        assert_eq!(layout(&mut FirstFit), ("03321.......".to_string(), 3));
+        assert_eq!(layout(&mut BestFit), ("021...33....".to_string(), 3));
+        assert_eq!(layout(&mut WorstFit), ("03321.......".to_string(), 3));
+        assert_eq!(
+            layout(&mut NextFit::default()),
+            ("03321.......".to_string(), 3)
+        );
+        assert_eq!(
+            layout(&mut ReducesFragmentation),
+            ("01......2.33".to_string(), 1)
+        );
+    }
+
+    #[test]
+    fn filesystem_test() {
+        let mut fb = FileBlocks::new(10);
+        assert_eq!(fb.create(3), Ok(0));
+        assert_eq!(fb.create(2), Ok(1));
+        assert_eq!(fb.create(3), Ok(2));
+        assert_eq!(fb.get_string_if_possible().unwrap(), "00011222..");
+        assert_eq!(fb.create(3), Err("disk full"));
+
+        fb.delete(1).unwrap();
+        assert_eq!(fb.delete(1), Err("no such file"));
+        assert_eq!(fb.get_string_if_possible().unwrap(), "000..222..");
+
+        fb.grow(2, 1).unwrap();
+        assert_eq!(fb.get_string_if_possible().unwrap(), "000..2222.");
+        fb.grow(0, 3).unwrap();
+        assert_eq!(fb.get_string_if_possible().unwrap(), "0000022220");
+        assert_eq!(fb.grow(0, 1), Err("disk full"));
+
+        fb.delete(2).unwrap();
+        assert_eq!(fb.create(1), Ok(3));
+        assert_eq!(fb.get_string_if_possible().unwrap(), "000003...0");
+        fb.defragment();
+        assert_eq!(fb.get_string_if_possible().unwrap(), "0000003...");
+
+        let mut fb = FileBlocks::from_disk_map(&[1, 1, 1]);
+        assert_eq!(fb.create(1), Ok(2));
+        assert_eq!(fb.create(1), Err("disk full"));
+        fb.delete(0).unwrap();
+        assert_eq!(fb.create(1), Ok(3));
+        assert_eq!(fb.get_string_if_possible().unwrap(), "321");
+
+        let mut fb = FileBlocks::new(2);
+        let empty = fb.create(0).unwrap();
+        fb.delete(empty).unwrap();
+        assert!(fb.spans_of(empty).is_empty());
+        assert_eq!(fb.delete(empty), Err("no such file"));
+        assert_eq!(fb.blocks, [Block::new(2, BlockKind::Empty)]);
+
+        // growing in place leaves no empty fragment behind
+        let mut fb = FileBlocks::new(10);
+        fb.create(2).unwrap();
+        fb.create(1).unwrap();
+        fb.create(1).unwrap();
+        fb.delete(1).unwrap();
+        fb.grow(0, 1).unwrap();
+        assert_eq!(
+            fb.blocks,
+            [
+                Block::new(3, BlockKind::Used(0)),
+                Block::new(1, BlockKind::Used(2)),
+                Block::new(6, BlockKind::Empty)
+            ]
+        );
+        assert_eq!(fb.spans_of(0), [Span::new(0, 3)]);
+        fb.grow(0, 0).unwrap();
+        assert_eq!(fb.spans_of(0), [Span::new(0, 3)]);
+        let mut events = Vec::new();
+        fb.compact_whole_with_events(|event| events.push(event));
+        assert!(events.is_empty());
+    }
+
+    #[test]
+    fn move_events_test() {
+        let disk_map = [1, 2, 3, 4, 5];
+        let mut fb = FileBlocks::from_disk_map(&disk_map);
+        let mut events = Vec::new();
+        fb.compact_fragmented_with_events(|event| events.push(event));
+
+        let layouts: Vec<String> = FileBlocks::from_disk_map(&disk_map)
+            .replay(&events)
+            .map(|layout| layout.unwrap().get_string_if_possible().unwrap())
+            .collect();
+        assert_eq!(layouts, ["022111....222..", "022111222......"]);
+        assert_eq!(layouts[1], fb.get_string_if_possible().unwrap());
+
+        let mut fb = FileBlocks::from_disk_map(&EXAMPLE);
+        let mut events = Vec::new();
+        fb.compact_whole_with_events(|event| events.push(event));
+        assert_eq!(
+            events[0],
+            MoveEvent {
+                file_id: 9,
+                from: 40,
+                to: 2,
+                len: 2
+            }
+        );
+
+        let layouts: Vec<FileBlocks> = FileBlocks::from_disk_map(&EXAMPLE)
+            .replay(&events)
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(layouts.len(), 4);
+        assert_eq!(
+            layouts[0].get_string_if_possible().unwrap(),
+            "0099.111...2...333.44.5555.6666.777.8888.."
+        );
+        assert_eq!(layouts[3].checksum(), fb.checksum());
+
+        let mut fb = FileBlocks::from_disk_map(&EXAMPLE);
+        fb.apply(&events[0]).unwrap();
+        assert_eq!(fb.apply(&events[0]), Err("source is not the file"));
+        let back = MoveEvent {
+            file_id: 9,
+            from: 2,
+            to: 0,
+            len: 2,
+        };
+        assert_eq!(fb.apply(&back), Err("destination is not free"));
+    }
+
+    #[test]
+    fn rendering_test() {
+        let mut fb = FileBlocks::new(5);
+        for _ in 0..10 {
+            fb.create(0).unwrap();
+        }
+        fb.create(1).unwrap();
+        fb.create(2).unwrap();
+        fb.delete(10).unwrap();
+        fb.create(1).unwrap();
+        assert_eq!(fb.get_string_if_possible(), None);
+        assert_eq!(fb.to_string(), "[12][11][11]..");
+        assert_eq!(
+            fb.render(Rendering::Alphabet("abcdefghijklm")).to_string(),
+            "mll.."
+        );
+        assert_eq!(
+            fb.render(Rendering::Bracketed).to_string(),
+            "[12][11][11].."
+        );
+        assert_eq!(FileBlocks::from_disk_map(&[1, 1, 1]).to_string(), "0.1");
+    }
+
+    #[test]
+    fn disk_map_test() {
+        assert_eq!(FileBlocks::from_disk_map(&EXAMPLE).to_disk_map(), EXAMPLE);
+
+        let mut fb = FileBlocks::from_disk_map(&EXAMPLE);
+        fb.compact_fragmented();
+        assert_eq!(
+            fb.to_disk_map(),
+            [2, 0, 2, 0, 1, 0, 3, 0, 3, 0, 1, 0, 3, 0, 3, 0, 1, 0, 2, 0, 1, 0, 4, 0, 2, 9, 0, 5]
+        );
+
+        // only the ids change on the way back
+        let round_trip = FileBlocks::from_disk_map(&fb.to_disk_map());
+        assert_eq!(round_trip.to_disk_map(), fb.to_disk_map());
+        assert!(fb
+            .diff(&round_trip)
+            .iter()
+            .all(|change| change.before.is_some() && change.after.is_some()));
+
+        let fb = FileBlocks::from_disk_map(&[12, 10]);
+        assert_eq!(fb.to_disk_map(), [9, 0, 3, 9, 0, 1]);
+    }
+
+    #[test]
+    fn diff_test() {
+        let original = FileBlocks::from_disk_map(&EXAMPLE);
+        let mut compacted = original.clone();
+        compacted.compact_whole();
+
+        let changes = original.diff(&compacted);
+        assert_eq!(changes.len(), 8);
+        assert_eq!(
+            changes[0],
+            LayoutChange {
+                span: Span::new(2, 2),
+                before: None,
+                after: Some(9)
+            }
+        );
+        assert_eq!(
+            changes[7],
+            LayoutChange {
+                span: Span::new(40, 2),
+                before: Some(9),
+                after: None
+            }
+        );
+        assert!(original.diff(&original).is_empty());
+
+        let short = FileBlocks::from_disk_map(&[1]);
+        assert_eq!(
+            short.diff(&FileBlocks::from_disk_map(&[1, 1, 1])),
+            [LayoutChange {
+                span: Span::new(2, 1),
+                before: None,
+                after: Some(1)
+            }]
+        );
+    }
+
+    #[test]
+    fn stats_test() {
+        let mut fb = FileBlocks::from_disk_map(&EXAMPLE);
+        let stats = fb.stats();
+        assert!(stats.fragments.values().all(|&n| n == 1));
+        assert_eq!((stats.largest_free_span, stats.total_free), (3, 14));
+        assert_eq!(stats.fragmentation, 1.0 - 3.0 / 14.0);
+        assert_eq!(stats.average_seek_distance, 0.0);
+
+        // 0099811188827773336446555566..............
+        fb.compact_fragmented();
+        let stats = fb.stats();
+        assert_eq!(stats.fragments[&8], 2);
+        assert_eq!(stats.fragments[&6], 3);
+        assert_eq!(stats.fragments.values().sum::<usize>(), 13);
+        assert_eq!((stats.largest_free_span, stats.total_free), (14, 14));
+        assert_eq!(stats.fragmentation, 0.0);
+        // file 8 skips 3 blocks and file 6 skips 2 and then 4
+        assert_eq!(stats.average_seek_distance, 0.9);
+
+        // 0.....1, with an empty file in the middle of the free space
+        let stats = FileBlocks::from_disk_map(&[1, 2, 0, 3, 1]).stats();
+        assert_eq!((stats.largest_free_span, stats.total_free), (5, 5));
+        assert_eq!(stats.fragmentation, 0.0);
+    }
+
+    #[test]
+    fn compact_fragmented_test() {
+        let mut fb = FileBlocks::from_disk_map(&EXAMPLE);
+        fb.compact_fragmented();
+
+        assert_eq!(
+            fb.get_string_if_possible().unwrap(),
+            "0099811188827773336446555566.............."
+        );
+        assert_eq!(fb.checksum(), 1928);
+    }
+
+    #[test]
+    fn long_free_spans_test() {
+        let mut fb = FileBlocks::from_disk_map(&[1, 200_000_000, 1]);
+        fb.compact_whole();
+        assert_eq!(fb.checksum(), 1);
+
+        let mut fb = FileBlocks::new(1_000_000_000);
+        fb.create(3).unwrap();
+        fb.compact_whole();
+        assert_eq!(fb.stats().total_free, 999_999_997);
+    }
+
+    #[test]
+    fn long_spans_test() {
+        let disk_map = [1_000_000_000, 1_000_000_000, 1_000_000_000];
+        let mut fb = FileBlocks::from_disk_map(&disk_map);
+        assert_eq!(
+            fb.checksum(),
+            2 * 1_000_000_000 * 1_000_000_000 + 499_999_999_500_000_000
+        );
+
+        fb.compact_fragmented();
+        assert_eq!(fb.checksum(), 1_499_999_999_500_000_000);
+
+        // longer than the square root of 'usize::MAX'
+        assert_eq!(FileBlocks::from_disk_map(&[5_000_000_000]).checksum(), 0);
+        assert_eq!(
+            FileBlocks::from_disk_map(&[1, 0, 5_000_000_000]).checksum(),
+            12_500_000_002_500_000_000
+        );
+    }
+}
@@ -1,131 +1,1011 @@
-use std::iter;
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::{Debug, Display},
+    hash::Hash,
+    ops::BitXor,
+};
 
-pub struct Equation {
-    numbers: Vec<i64>,
+pub struct Equation<N = i64> {
+    numbers: Vec<N>,
 }
 
-impl Equation {
-    pub fn new(numbers: Vec<i64>) -> Self {
+impl<N: Number> Equation<N> {
+    pub fn new(numbers: Vec<N>) -> Self {
         Self { numbers }
     }
 
-    pub fn all_results(&self, operations: &[Operation], max_result: i64) -> Vec<i64> {
-        let mut results = Vec::with_capacity(2_usize.pow(self.numbers.len() as u32 - 1));
-        Self::all_results_rec(0, &self.numbers, &mut results, operations, max_result);
+    /// Whether a partial result past the target can be dropped, which [`Operator::never_decreases`]
+    /// only guarantees when every number is positive.
+    fn can_prune(&self, operators: &[&dyn Operator<N>]) -> bool {
+        operators.iter().all(|o| o.never_decreases())
+            && self.numbers.iter().all(|&number| number > N::from(0))
+    }
+
+    /// Lazily evaluates every combination of operators from left to right, yielding each
+    /// result that is at most `max_result`. Branches that overflow `N` are skipped.
+    pub fn all_results<'a>(
+        &'a self,
+        operators: &'a [&'a dyn Operator<N>],
+        max_result: N,
+    ) -> Results<'a, N> {
+        let stack = match self.numbers.first() {
+            Some(&first) => vec![(first, 1)],
+            None => Vec::new(),
+        };
+
+        Results {
+            numbers: &self.numbers,
+            operators,
+            max_result,
+            prune: self.can_prune(operators),
+            stack,
+        }
+    }
+
+    /// Like [`Equation::all_results`], but merges identical partial results after every number,
+    /// so memory is bounded by the number of distinct values rather than combinations.
+    pub fn distinct_results(&self, operators: &[&dyn Operator<N>], max_result: N) -> HashSet<N> {
+        let Some((&first, rest)) = self.numbers.split_first() else {
+            return HashSet::new();
+        };
+        let prune = self.can_prune(operators);
+
+        let mut results = HashSet::from([first]);
+        for &number in rest {
+            let mut next_results = HashSet::with_capacity(results.len());
+            for &result in &results {
+                for operator in operators {
+                    if let Ok(next) = operator.apply(result, number) {
+                        if !prune || next <= max_result {
+                            next_results.insert(next);
+                        }
+                    }
+                }
+            }
+            results = next_results;
+        }
+        results.retain(|&result| result <= max_result);
 
         results
     }
 
-    pub fn can_result_in(&self, operations: &[Operation], target: i64) -> bool {
-        Self::find_result_rec(0, &self.numbers, operations, target)
+    pub fn can_result_in(&self, operators: &[&dyn Operator<N>], target: N) -> bool {
+        self.find_grouping(operators, target, Evaluation::LeftToRight)
+            .is_some()
+    }
+
+    /// Finds a way to place the operators between the numbers so that the equation evaluates to
+    /// `target` under the given evaluation rules, and returns it as a fully parenthesised
+    /// expression. Branches that overflow `N` are skipped.
+    pub fn find_grouping<'a>(
+        &self,
+        operators: &[&'a dyn Operator<N>],
+        target: N,
+        evaluation: Evaluation,
+    ) -> Option<Expression<'a, N>> {
+        self.search(operators, target, evaluation, false)
+            .ok()
+            .flatten()
+    }
+
+    /// Like [`Equation::find_grouping`], but fails with [`OperatorError::Overflow`] as soon as
+    /// any evaluated branch overflows `N`, instead of skipping it.
+    pub fn try_find_grouping<'a>(
+        &self,
+        operators: &[&'a dyn Operator<N>],
+        target: N,
+        evaluation: Evaluation,
+    ) -> Result<Option<Expression<'a, N>>, OperatorError> {
+        self.search(operators, target, evaluation, true)
+    }
+
+    fn search<'a>(
+        &self,
+        operators: &[&'a dyn Operator<N>],
+        target: N,
+        evaluation: Evaluation,
+        strict: bool,
+    ) -> Result<Option<Expression<'a, N>>, OperatorError> {
+        if self.numbers.is_empty() {
+            return Ok(None);
+        }
+
+        match evaluation {
+            Evaluation::LeftToRight => self.find_left_to_right(operators, target, strict),
+            Evaluation::Precedence => self.find_with_precedence(operators, target, strict),
+            Evaluation::AnyGrouping => self.find_any_grouping(operators, target, strict),
+        }
+    }
+
+    fn find_left_to_right<'a>(
+        &self,
+        operators: &[&'a dyn Operator<N>],
+        target: N,
+        strict: bool,
+    ) -> Result<Option<Expression<'a, N>>, OperatorError> {
+        let mut choices = Vec::with_capacity(self.numbers.len());
+        // the reverse search never computes a value outside of N, so it can't overflow
+        let found = if operators.iter().all(|o| o.is_invertible())
+            && self.numbers[1..].iter().all(|&n| n != N::from(0))
+        {
+            let found = Self::find_result_rev(target, &self.numbers, operators, &mut choices);
+            choices.reverse();
+            found
+        } else {
+            let prune = self.can_prune(operators);
+            Self::find_result_rec(
+                self.numbers[0],
+                &self.numbers[1..],
+                operators,
+                target,
+                prune,
+                strict,
+                &mut choices,
+            )?
+        };
+        if !found {
+            return Ok(None);
+        }
+
+        let mut expression = Expression::Number(self.numbers[0]);
+        for (&operator, &number) in choices.iter().zip(&self.numbers[1..]) {
+            expression = Expression::Apply(
+                operators[operator],
+                Box::new(expression),
+                Box::new(Expression::Number(number)),
+            );
+        }
+        Ok(Some(expression))
     }
 
     fn find_result_rec(
-        result_so_far: i64,
-        numbers: &[i64],
-        operations: &[Operation],
-        find_result: i64,
+        result_so_far: N,
+        numbers: &[N],
+        operators: &[&dyn Operator<N>],
+        find_result: N,
+        prune: bool,
+        strict: bool,
+        choices: &mut Vec<usize>,
+    ) -> Result<bool, OperatorError> {
+        if prune && result_so_far > find_result {
+            return Ok(false);
+        }
+        if numbers.is_empty() {
+            return Ok(result_so_far == find_result);
+        }
+        for (i, operator) in operators.iter().enumerate() {
+            let Some(next) = Self::apply(*operator, result_so_far, numbers[0], strict)? else {
+                continue;
+            };
+            choices.push(i);
+            if Self::find_result_rec(
+                next,
+                &numbers[1..],
+                operators,
+                find_result,
+                prune,
+                strict,
+                choices,
+            )? {
+                return Ok(true);
+            }
+            choices.pop();
+        }
+
+        Ok(false)
+    }
+
+    /// Works backwards from the target, undoing the last number with each operator's inverse.
+    /// Branches where no operator can be undone are cut immediately.
+    fn find_result_rev(
+        target: N,
+        numbers: &[N],
+        operators: &[&dyn Operator<N>],
+        choices: &mut Vec<usize>,
     ) -> bool {
-        if result_so_far > find_result {
+        let Some((&last, rest)) = numbers.split_last() else {
             return false;
+        };
+        if rest.is_empty() {
+            return target == last;
         }
-        if numbers.is_empty() {
-            return result_so_far == find_result;
-        }
-        for operation in operations {
-            match operation {
-                Operation::Addition => {
-                    if Self::find_result_rec(
-                        result_so_far + numbers[0],
-                        &numbers[1..],
-                        operations,
-                        find_result,
-                    ) {
-                        return true;
-                    }
+        for (i, operator) in operators.iter().enumerate() {
+            if let Some(previous) = operator.inverse(target, last) {
+                choices.push(i);
+                if Self::find_result_rev(previous, rest, operators, choices) {
+                    return true;
                 }
-                Operation::Multiplication => {
-                    if Self::find_result_rec(
-                        result_so_far * numbers[0],
-                        &numbers[1..],
-                        operations,
-                        find_result,
-                    ) {
-                        return true;
+                choices.pop();
+            }
+        }
+
+        false
+    }
+
+    fn find_with_precedence<'a>(
+        &self,
+        operators: &[&'a dyn Operator<N>],
+        target: N,
+        strict: bool,
+    ) -> Result<Option<Expression<'a, N>>, OperatorError> {
+        let mut chosen = Vec::with_capacity(self.numbers.len());
+        Self::find_with_precedence_rec(&self.numbers, operators, target, strict, &mut chosen)
+    }
+
+    fn find_with_precedence_rec<'a>(
+        numbers: &[N],
+        operators: &[&'a dyn Operator<N>],
+        target: N,
+        strict: bool,
+        chosen: &mut Vec<&'a dyn Operator<N>>,
+    ) -> Result<Option<Expression<'a, N>>, OperatorError> {
+        if chosen.len() + 1 == numbers.len() {
+            let expression = Expression::with_precedence(numbers, chosen);
+            return match expression.evaluate() {
+                Ok(result) if result == target => Ok(Some(expression)),
+                Err(OperatorError::Overflow) if strict => Err(OperatorError::Overflow),
+                _ => Ok(None),
+            };
+        }
+        for &operator in operators {
+            chosen.push(operator);
+            if let Some(expression) =
+                Self::find_with_precedence_rec(numbers, operators, target, strict, chosen)?
+            {
+                return Ok(Some(expression));
+            }
+            chosen.pop();
+        }
+
+        Ok(None)
+    }
+
+    /// Interval DP over every parenthesisation: `reachable[start][len - 1]` maps each value that
+    /// `numbers[start..start + len]` can take to the split that produced it, so a matching
+    /// grouping can be rebuilt from the top.
+    fn find_any_grouping<'a>(
+        &self,
+        operators: &[&'a dyn Operator<N>],
+        target: N,
+        strict: bool,
+    ) -> Result<Option<Expression<'a, N>>, OperatorError> {
+        let n = self.numbers.len();
+        let prune = self.can_prune(operators);
+
+        let mut reachable: Reachable<N> = vec![Vec::new(); n];
+        for (start, &number) in self.numbers.iter().enumerate() {
+            reachable[start].push(HashMap::from([(number, None)]));
+        }
+        for len in 2..=n {
+            for start in 0..=(n - len) {
+                let mut values = HashMap::new();
+                for left_len in 1..len {
+                    let right_start = start + left_len;
+                    let right_len = len - left_len;
+                    for &lhs in reachable[start][left_len - 1].keys() {
+                        for &rhs in reachable[right_start][right_len - 1].keys() {
+                            for (operator, o) in operators.iter().enumerate() {
+                                let Some(value) = Self::apply(*o, lhs, rhs, strict)? else {
+                                    continue;
+                                };
+                                if prune && value > target {
+                                    continue;
+                                }
+                                values.entry(value).or_insert(Some(Split {
+                                    left_len,
+                                    operator,
+                                    lhs,
+                                    rhs,
+                                }));
+                            }
+                        }
                     }
                 }
-                Operation::Concatenation => {
-                    if Self::find_result_rec(
-                        Self::concatenate(result_so_far, numbers[0]),
-                        &numbers[1..],
-                        operations,
-                        find_result,
-                    ) {
-                        return true;
-                    }
+                reachable[start].push(values);
+            }
+        }
+
+        if !reachable[0][n - 1].contains_key(&target) {
+            return Ok(None);
+        }
+        Ok(Some(Self::rebuild_grouping(
+            &reachable, operators, 0, n, target,
+        )))
+    }
+
+    fn rebuild_grouping<'a>(
+        reachable: &Reachable<N>,
+        operators: &[&'a dyn Operator<N>],
+        start: usize,
+        len: usize,
+        value: N,
+    ) -> Expression<'a, N> {
+        match reachable[start][len - 1][&value] {
+            None => Expression::Number(value),
+            Some(split) => Expression::Apply(
+                operators[split.operator],
+                Box::new(Self::rebuild_grouping(
+                    reachable,
+                    operators,
+                    start,
+                    split.left_len,
+                    split.lhs,
+                )),
+                Box::new(Self::rebuild_grouping(
+                    reachable,
+                    operators,
+                    start + split.left_len,
+                    len - split.left_len,
+                    split.rhs,
+                )),
+            ),
+        }
+    }
+
+    /// Applies the operator, turning errors into a skipped branch unless `strict` is set and
+    /// the error is an overflow.
+    fn apply(
+        operator: &dyn Operator<N>,
+        lhs: N,
+        rhs: N,
+        strict: bool,
+    ) -> Result<Option<N>, OperatorError> {
+        match operator.apply(lhs, rhs) {
+            Ok(result) => Ok(Some(result)),
+            Err(OperatorError::Overflow) if strict => Err(OperatorError::Overflow),
+            Err(_) => Ok(None),
+        }
+    }
+}
+
+/// Checks which of the `(target, equation)` pairs can be made true with the operators, spreading
+/// the equations over `workers` threads. The output is in the same order as the input regardless
+/// of how the work was split.
+pub fn calibrate<N: Number>(
+    equations: &[(N, Equation<N>)],
+    operators: &[&dyn Operator<N>],
+    workers: usize,
+) -> Result<Calibration<N>, OperatorError> {
+    let chunk_size = equations.len().div_ceil(workers.max(1)).max(1);
+
+    let passed: Vec<bool> = std::thread::scope(|scope| {
+        let handles: Vec<_> = equations
+            .chunks(chunk_size)
+            .map(|chunk| {
+                scope.spawn(move || {
+                    chunk
+                        .iter()
+                        .map(|(target, equation)| equation.can_result_in(operators, *target))
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect();
+
+        handles
+            .into_iter()
+            .flat_map(|handle| handle.join().unwrap())
+            .collect()
+    });
+
+    let mut total = N::from(0);
+    for ((target, _), &passed) in equations.iter().zip(&passed) {
+        if passed {
+            total = total.checked_add(*target).ok_or(OperatorError::Overflow)?;
+        }
+    }
+
+    Ok(Calibration { total, passed })
+}
+
+pub struct Calibration<N = i64> {
+    total: N,
+    passed: Vec<bool>,
+}
+
+impl<N: Number> Calibration<N> {
+    /// The sum of the targets of all equations that passed.
+    pub fn total(&self) -> N {
+        self.total
+    }
+
+    /// Whether each equation passed, in input order.
+    pub fn passed(&self) -> &[bool] {
+        &self.passed
+    }
+}
+
+/// Depth-first iterator over the results of [`Equation::all_results`]. The stack holds at most
+/// one entry per operator for each number, so memory grows linearly with the equation length.
+pub struct Results<'a, N> {
+    numbers: &'a [N],
+    operators: &'a [&'a dyn Operator<N>],
+    max_result: N,
+    prune: bool,
+    /// Partial results together with how many numbers they have consumed.
+    stack: Vec<(N, usize)>,
+}
+
+impl<N: Number> Iterator for Results<'_, N> {
+    type Item = N;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some((result_so_far, consumed)) = self.stack.pop() {
+            if self.prune && result_so_far > self.max_result {
+                continue;
+            }
+            let Some(&number) = self.numbers.get(consumed) else {
+                if result_so_far <= self.max_result {
+                    return Some(result_so_far);
+                }
+                continue;
+            };
+            // push in reverse so that the first operator is explored first
+            for operator in self.operators.iter().rev() {
+                if let Ok(next) = operator.apply(result_so_far, number) {
+                    self.stack.push((next, consumed + 1));
                 }
             }
         }
 
-        return false;
+        None
     }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Evaluation {
+    /// Every operator is applied in order, ignoring precedence.
+    LeftToRight,
+    /// Operators with a higher [`Operator::precedence`] are applied first, then left to right.
+    Precedence,
+    /// Any parenthesisation of the numbers is allowed.
+    AnyGrouping,
+}
+
+/// For every start index and length, the reachable values and how each was produced.
+type Reachable<N> = Vec<Vec<HashMap<N, Option<Split<N>>>>>;
+
+#[derive(Clone, Copy, Debug)]
+struct Split<N> {
+    left_len: usize,
+    operator: usize,
+    lhs: N,
+    rhs: N,
+}
+
+#[derive(Clone)]
+pub enum Expression<'a, N = i64> {
+    Number(N),
+    Apply(
+        &'a dyn Operator<N>,
+        Box<Expression<'a, N>>,
+        Box<Expression<'a, N>>,
+    ),
+}
+
+impl<'a, N: Number> Expression<'a, N> {
+    fn with_precedence(numbers: &[N], operators: &[&'a dyn Operator<N>]) -> Self {
+        let mut operands = vec![Expression::Number(numbers[0])];
+        let mut pending: Vec<&'a dyn Operator<N>> = Vec::with_capacity(operators.len());
 
-    fn all_results_rec(
-        result_so_far: i64,
-        numbers: &[i64],
-        results: &mut Vec<i64>,
-        operations: &[Operation],
-        max_result: i64,
-    ) {
-        if result_so_far > max_result {
-            return;
+        fn reduce<'a, N: Number>(
+            operands: &mut Vec<Expression<'a, N>>,
+            pending: &mut Vec<&'a dyn Operator<N>>,
+        ) {
+            let rhs = operands.pop().unwrap();
+            let lhs = operands.pop().unwrap();
+            operands.push(Expression::Apply(
+                pending.pop().unwrap(),
+                Box::new(lhs),
+                Box::new(rhs),
+            ));
         }
-        if numbers.is_empty() {
-            results.push(result_so_far);
-            return;
-        }
-        for operation in operations {
-            match operation {
-                Operation::Addition => Self::all_results_rec(
-                    result_so_far + numbers[0],
-                    &numbers[1..],
-                    results,
-                    operations,
-                    max_result,
-                ),
-                Operation::Multiplication => Self::all_results_rec(
-                    result_so_far * numbers[0],
-                    &numbers[1..],
-                    results,
-                    operations,
-                    max_result,
-                ),
-                Operation::Concatenation => Self::all_results_rec(
-                    Self::concatenate(result_so_far, numbers[0]),
-                    &numbers[1..],
-                    results,
-                    operations,
-                    max_result,
-                ),
+
+        for (&operator, &number) in operators.iter().zip(&numbers[1..]) {
+            // an operator of equal precedence waits if it groups to the right
+            while pending.last().is_some_and(|top| {
+                top.precedence() > operator.precedence()
+                    || (top.precedence() == operator.precedence()
+                        && !operator.is_right_associative())
+            }) {
+                reduce(&mut operands, &mut pending);
+            }
+            pending.push(operator);
+            operands.push(Expression::Number(number));
+        }
+        while !pending.is_empty() {
+            reduce(&mut operands, &mut pending);
+        }
+
+        operands.pop().unwrap()
+    }
+
+    pub fn evaluate(&self) -> Result<N, OperatorError> {
+        match self {
+            Expression::Number(n) => Ok(*n),
+            Expression::Apply(operator, lhs, rhs) => {
+                operator.apply(lhs.evaluate()?, rhs.evaluate()?)
             }
         }
     }
+}
 
-    fn concatenate(a: i64, b: i64) -> i64 {
-        format!("{}{}", a, b).parse().unwrap()
+impl<N: Number> Display for Expression<'_, N> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Expression::Number(n) => write!(f, "{}", n),
+            Expression::Apply(operator, lhs, rhs) => {
+                write!(f, "({} {} {})", lhs, operator.symbol(), rhs)
+            }
+        }
     }
 }
 
-pub enum Operation {
-    Addition,
-    Multiplication,
-    Concatenation,
+/// The integer type an [`Equation`] is evaluated in. Use `i128` for inputs whose targets don't
+/// fit in `i64`.
+pub trait Number:
+    Copy + Ord + Hash + Debug + Display + Send + Sync + From<i64> + BitXor<Output = Self> + TryInto<u32>
+{
+    fn checked_add(self, rhs: Self) -> Option<Self>;
+    fn checked_sub(self, rhs: Self) -> Option<Self>;
+    fn checked_mul(self, rhs: Self) -> Option<Self>;
+    fn checked_div(self, rhs: Self) -> Option<Self>;
+    fn checked_rem(self, rhs: Self) -> Option<Self>;
+    fn checked_pow(self, exp: u32) -> Option<Self>;
+}
+
+macro_rules! impl_number {
+    ($($t:ty),*) => {
+        $(
+            impl Number for $t {
+                fn checked_add(self, rhs: Self) -> Option<Self> {
+                    <$t>::checked_add(self, rhs)
+                }
+
+                fn checked_sub(self, rhs: Self) -> Option<Self> {
+                    <$t>::checked_sub(self, rhs)
+                }
+
+                fn checked_mul(self, rhs: Self) -> Option<Self> {
+                    <$t>::checked_mul(self, rhs)
+                }
+
+                fn checked_div(self, rhs: Self) -> Option<Self> {
+                    <$t>::checked_div(self, rhs)
+                }
+
+                fn checked_rem(self, rhs: Self) -> Option<Self> {
+                    <$t>::checked_rem(self, rhs)
+                }
+
+                fn checked_pow(self, exp: u32) -> Option<Self> {
+                    <$t>::checked_pow(self, exp)
+                }
+            }
+        )*
+    };
+}
+
+impl_number!(i64, i128);
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum OperatorError {
+    /// The operator isn't defined for the operands, like division by zero.
+    Undefined,
+    /// The result doesn't fit in the number type.
+    Overflow,
 }
 
-impl Operation {
-    pub fn iter() -> impl Iterator<Item = Self> {
-        iter::once(Self::Addition)
-            .chain(iter::once(Self::Multiplication))
-            .chain(iter::once(Self::Concatenation))
+/// A binary operator that can be placed between two numbers of an [`Equation`].
+pub trait Operator<N: Number = i64>: Sync {
+    /// Combines the result so far with the next number.
+    fn apply(&self, lhs: N, rhs: N) -> Result<N, OperatorError>;
+
+    fn symbol(&self) -> &str;
+
+    /// Operators with higher precedence bind tighter under [`Evaluation::Precedence`].
+    fn precedence(&self) -> u8 {
+        0
+    }
+
+    /// Whether a chain of this operator groups from the right under [`Evaluation::Precedence`],
+    /// like `2 ^ 3 ^ 2 == 2 ^ (3 ^ 2)`. Operators group from the left otherwise.
+    fn is_right_associative(&self) -> bool {
+        false
+    }
+
+    /// Returns the `lhs` for which `apply(lhs, rhs) == result`, or `None` if there is none.
+    /// Only called with a non-zero `rhs`, and only if [`Operator::is_invertible`] returns `true`.
+    fn inverse(&self, result: N, rhs: N) -> Option<N> {
+        None
+    }
+
+    fn is_invertible(&self) -> bool {
+        false
+    }
+
+    /// Whether `apply(lhs, rhs) >= max(lhs, rhs)` for all positive operands, which allows
+    /// pruning any branch that has already passed the target.
+    fn never_decreases(&self) -> bool {
+        false
+    }
+}
+
+pub struct Addition;
+
+impl<N: Number> Operator<N> for Addition {
+    fn apply(&self, lhs: N, rhs: N) -> Result<N, OperatorError> {
+        lhs.checked_add(rhs).ok_or(OperatorError::Overflow)
+    }
+
+    fn symbol(&self) -> &str {
+        "+"
+    }
+
+    fn inverse(&self, result: N, rhs: N) -> Option<N> {
+        result.checked_sub(rhs)
+    }
+
+    fn is_invertible(&self) -> bool {
+        true
+    }
+
+    fn never_decreases(&self) -> bool {
+        true
+    }
+}
+
+pub struct Subtraction;
+
+impl<N: Number> Operator<N> for Subtraction {
+    fn apply(&self, lhs: N, rhs: N) -> Result<N, OperatorError> {
+        lhs.checked_sub(rhs).ok_or(OperatorError::Overflow)
+    }
+
+    fn symbol(&self) -> &str {
+        "-"
+    }
+
+    fn inverse(&self, result: N, rhs: N) -> Option<N> {
+        result.checked_add(rhs)
+    }
+
+    fn is_invertible(&self) -> bool {
+        true
+    }
+}
+
+pub struct Multiplication;
+
+impl<N: Number> Operator<N> for Multiplication {
+    fn apply(&self, lhs: N, rhs: N) -> Result<N, OperatorError> {
+        lhs.checked_mul(rhs).ok_or(OperatorError::Overflow)
+    }
+
+    fn symbol(&self) -> &str {
+        "*"
+    }
+
+    fn precedence(&self) -> u8 {
+        1
+    }
+
+    fn inverse(&self, result: N, rhs: N) -> Option<N> {
+        if result.checked_rem(rhs)? == N::from(0) {
+            result.checked_div(rhs)
+        } else {
+            None
+        }
+    }
+
+    fn is_invertible(&self) -> bool {
+        true
+    }
+
+    fn never_decreases(&self) -> bool {
+        true
+    }
+}
+
+/// Integer division, rounding towards zero.
+pub struct Division;
+
+impl<N: Number> Operator<N> for Division {
+    fn apply(&self, lhs: N, rhs: N) -> Result<N, OperatorError> {
+        if rhs == N::from(0) {
+            return Err(OperatorError::Undefined);
+        }
+        lhs.checked_div(rhs).ok_or(OperatorError::Overflow)
+    }
+
+    fn symbol(&self) -> &str {
+        "/"
+    }
+
+    fn precedence(&self) -> u8 {
+        1
+    }
+}
+
+pub struct Exponentiation;
+
+impl<N: Number> Operator<N> for Exponentiation {
+    fn apply(&self, lhs: N, rhs: N) -> Result<N, OperatorError> {
+        if rhs < N::from(0) {
+            return Err(OperatorError::Undefined);
+        }
+        let exp = rhs.try_into().map_err(|_| OperatorError::Overflow)?;
+        lhs.checked_pow(exp).ok_or(OperatorError::Overflow)
+    }
+
+    fn symbol(&self) -> &str {
+        "^"
+    }
+
+    fn precedence(&self) -> u8 {
+        2
+    }
+
+    fn is_right_associative(&self) -> bool {
+        true
+    }
+}
+
+pub struct Xor;
+
+impl<N: Number> Operator<N> for Xor {
+    fn apply(&self, lhs: N, rhs: N) -> Result<N, OperatorError> {
+        Ok(lhs ^ rhs)
+    }
+
+    fn symbol(&self) -> &str {
+        "xor"
+    }
+
+    fn inverse(&self, result: N, rhs: N) -> Option<N> {
+        Some(result ^ rhs)
+    }
+
+    fn is_invertible(&self) -> bool {
+        true
+    }
+}
+
+/// Appends the digits of `rhs` to `lhs`, both written in `base`. Only defined for non-negative
+/// operands.
+pub struct Concatenation {
+    base: i64,
+}
+
+impl Concatenation {
+    pub fn new(base: i64) -> Self {
+        assert!(base >= 2, "base must be at least 2");
+        Self { base }
+    }
+
+    /// The smallest power of the base that is larger than `rhs`, or `None` if it overflows.
+    fn shift_for<N: Number>(&self, rhs: N) -> Option<N> {
+        let base = N::from(self.base);
+        let mut shift = base;
+        while shift <= rhs {
+            shift = shift.checked_mul(base)?;
+        }
+        Some(shift)
+    }
+}
+
+impl Default for Concatenation {
+    fn default() -> Self {
+        Self::new(10)
+    }
+}
+
+impl<N: Number> Operator<N> for Concatenation {
+    fn apply(&self, lhs: N, rhs: N) -> Result<N, OperatorError> {
+        if lhs < N::from(0) || rhs < N::from(0) {
+            return Err(OperatorError::Undefined);
+        }
+        self.shift_for(rhs)
+            .and_then(|shift| lhs.checked_mul(shift))
+            .and_then(|shifted| shifted.checked_add(rhs))
+            .ok_or(OperatorError::Overflow)
+    }
+
+    fn symbol(&self) -> &str {
+        "||"
+    }
+
+    fn inverse(&self, result: N, rhs: N) -> Option<N> {
+        if result < N::from(0) || rhs < N::from(0) {
+            return None;
+        }
+        let shift = self.shift_for(rhs)?;
+        if result.checked_rem(shift)? == rhs {
+            result.checked_div(shift)
+        } else {
+            None
+        }
+    }
+
+    fn is_invertible(&self) -> bool {
+        true
+    }
+
+    fn never_decreases(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn concatenation_test() {
+        let decimal: &dyn Operator = &Concatenation::default();
+        assert_eq!(decimal.apply(12, 345), Ok(12345));
+        assert_eq!(decimal.apply(12, 0), Ok(120));
+        assert_eq!(decimal.apply(-12, 0), Err(OperatorError::Undefined));
+        assert_eq!(decimal.inverse(12345, 345), Some(12));
+        assert_eq!(decimal.inverse(12345, 45), Some(123));
+        assert_eq!(decimal.inverse(12345, 44), None);
+
+        let binary: &dyn Operator = &Concatenation::new(2);
+        assert_eq!(binary.apply(0b101, 0b11), Ok(0b10111));
+        assert_eq!(binary.inverse(0b10111, 0b11), Some(0b101));
+    }
+
+    #[test]
+    fn reverse_search_test() {
+        let equation = Equation::new(vec![15_i64, 6]);
+        assert!(equation.can_result_in(&[&Addition, &Concatenation::default()], 156));
+        assert!(!equation.can_result_in(&[&Addition, &Multiplication], 156));
+
+        let equation = Equation::new(vec![10_i64, 3, 4]);
+        assert!(equation.can_result_in(&[&Subtraction, &Xor], 3));
+        assert!(equation.can_result_in(&[&Division, &Exponentiation], 81));
+        assert!(!equation.can_result_in(&[&Division, &Exponentiation], 82));
+    }
+
+    #[test]
+    fn evaluation_test() {
+        let operators: [&dyn Operator; 2] = [&Addition, &Multiplication];
+
+        let equation = Equation::new(vec![2, 3, 4]);
+        assert!(equation
+            .find_grouping(&operators, 14, Evaluation::LeftToRight)
+            .is_none());
+        let grouping = equation
+            .find_grouping(&operators, 14, Evaluation::Precedence)
+            .unwrap();
+        assert_eq!(grouping.to_string(), "(2 + (3 * 4))");
+        assert!(equation
+            .find_grouping(&operators, 20, Evaluation::Precedence)
+            .is_none());
+        let grouping = equation
+            .find_grouping(&operators, 20, Evaluation::LeftToRight)
+            .unwrap();
+        assert_eq!(grouping.to_string(), "((2 + 3) * 4)");
+
+        let equation = Equation::new(vec![2, 3, 4, 5]);
+        assert!(equation
+            .find_grouping(&operators, 70, Evaluation::LeftToRight)
+            .is_none());
+        assert!(equation
+            .find_grouping(&operators, 70, Evaluation::Precedence)
+            .is_none());
+        let grouping = equation
+            .find_grouping(&operators, 70, Evaluation::AnyGrouping)
+            .unwrap();
+        assert_eq!(grouping.evaluate(), Ok(70));
+    }
+
+    #[test]
+    fn overflow_test() {
+        let multiplication: &dyn Operator = &Multiplication;
+        assert_eq!(
+            multiplication.apply(i64::MAX, 2),
+            Err(OperatorError::Overflow)
+        );
+        let decimal: &dyn Operator = &Concatenation::default();
+        assert_eq!(
+            decimal.apply(i64::MAX / 10, 10),
+            Err(OperatorError::Overflow)
+        );
+
+        let operators: [&dyn Operator; 2] = [&Multiplication, &Division];
+        let equation = Equation::new(vec![i64::MAX, 2, 2]);
+        let grouping = equation
+            .find_grouping(&operators, i64::MAX - 1, Evaluation::LeftToRight)
+            .unwrap();
+        assert_eq!(grouping.to_string(), format!("(({} / 2) * 2)", i64::MAX));
+        assert_eq!(
+            equation
+                .try_find_grouping(&operators, i64::MAX - 1, Evaluation::LeftToRight)
+                .err(),
+            Some(OperatorError::Overflow)
+        );
+
+        let operators: [&dyn Operator<i128>; 2] = [&Addition, &Concatenation::default()];
+        let equation = Equation::new(vec![123456789012_i128, 345678901234, 5678901234]);
+        let target = 1234567890123456789012345678901234_i128;
+        assert!(equation.can_result_in(&operators, target));
+    }
+
+    #[test]
+    fn associativity_test() {
+        let equation = Equation::new(vec![2_i64, 3, 2]);
+
+        let power: [&dyn Operator; 1] = [&Exponentiation];
+        let grouping = equation
+            .find_grouping(&power, 512, Evaluation::Precedence)
+            .unwrap();
+        assert_eq!(grouping.to_string(), "(2 ^ (3 ^ 2))");
+        assert!(equation
+            .find_grouping(&power, 64, Evaluation::Precedence)
+            .is_none());
+
+        let minus: [&dyn Operator; 1] = [&Subtraction];
+        let grouping = equation
+            .find_grouping(&minus, -3, Evaluation::Precedence)
+            .unwrap();
+        assert_eq!(grouping.to_string(), "((2 - 3) - 2)");
+    }
+
+    #[test]
+    fn non_positive_test() {
+        let operators: [&dyn Operator; 2] = [&Addition, &Multiplication];
+        let equation = Equation::new(vec![5_i64, 0]);
+
+        assert!(equation.can_result_in(&operators, 0));
+        assert_eq!(
+            equation.all_results(&operators, 0).collect::<Vec<_>>(),
+            vec![0]
+        );
+        assert_eq!(equation.distinct_results(&operators, 0), HashSet::from([0]));
+        for evaluation in [
+            Evaluation::LeftToRight,
+            Evaluation::Precedence,
+            Evaluation::AnyGrouping,
+        ] {
+            let grouping = equation.find_grouping(&operators, 0, evaluation).unwrap();
+            assert_eq!(grouping.to_string(), "(5 * 0)");
+        }
+
+        let equation = Equation::new(vec![-3_i64, 2, 4]);
+        assert!(equation.can_result_in(&operators, -2));
+    }
+
+    #[test]
+    fn all_results_test() {
+        let operators: [&dyn Operator; 3] = [&Addition, &Multiplication, &Concatenation::default()];
+        let equation = Equation::new(vec![1_i64, 2, 3]);
+
+        let results: Vec<_> = equation.all_results(&operators, i64::MAX).collect();
+        assert_eq!(results, vec![6, 9, 33, 5, 6, 23, 15, 36, 123]);
+
+        let distinct = equation.distinct_results(&operators, 30);
+        assert_eq!(distinct, HashSet::from([5, 6, 9, 15, 23]));
+
+        let equation = Equation::new(vec![1_i64; 30]);
+        assert_eq!(equation.all_results(&operators, 1).count(), 1);
+        assert_eq!(equation.distinct_results(&operators, 1).len(), 1);
+    }
+
+    #[test]
+    fn calibrate_test() {
+        let operators: [&dyn Operator; 2] = [&Addition, &Multiplication];
+        let equations = vec![
+            (190, Equation::new(vec![10, 19])),
+            (83, Equation::new(vec![17, 5])),
+            (3267, Equation::new(vec![81, 40, 27])),
+            (292, Equation::new(vec![11, 6, 16, 20])),
+            (161011, Equation::new(vec![16, 10, 13])),
+        ];
+
+        for workers in [0, 1, 2, 3, 8] {
+            let calibration = calibrate(&equations, &operators, workers).unwrap();
+            assert_eq!(calibration.total(), 3749);
+            assert_eq!(calibration.passed(), &[true, false, true, true, false]);
+        }
     }
 }
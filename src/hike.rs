@@ -1,114 +1,926 @@
-use std::collections::{HashSet, VecDeque};
+use std::{
+    collections::{HashMap, VecDeque},
+    error::Error,
+    fmt::{Debug, Display},
+    ops::RangeInclusive,
+    str::FromStr,
+};
 
 use simple_grid::{Grid, GridIndex};
 
-pub struct TopographyMap {
-    grid: Grid<Cell>,
+/// A height on a [`TopographyMap`].
+pub trait Height: Copy + Ord + Debug + Into<i128> {}
+
+impl<T: Copy + Ord + Debug + Into<i128>> Height for T {}
+
+/// The cells a hiker can step to from where they are.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Neighborhood {
+    Four,
+    Eight,
+}
+
+/// How trails may climb, and where they start and end.
+pub struct HikeRules<H = u8> {
+    deltas: RangeInclusive<i128>,
+    neighborhood: Neighborhood,
+    is_trailhead: Box<dyn Fn(H) -> bool>,
+    is_peak: Box<dyn Fn(H) -> bool>,
+}
+
+impl<H: Height> HikeRules<H> {
+    /// Steps may change the height by any of `deltas`, so a negative start allows descents.
+    /// Trails start at height 0 and end at height 9.
+    pub fn new(deltas: RangeInclusive<i128>, neighborhood: Neighborhood) -> Self {
+        Self {
+            deltas,
+            neighborhood,
+            is_trailhead: Box::new(|height| height.into() == 0),
+            is_peak: Box::new(|height| height.into() == 9),
+        }
+    }
+
+    pub fn with_trailheads(mut self, is_trailhead: impl Fn(H) -> bool + 'static) -> Self {
+        self.is_trailhead = Box::new(is_trailhead);
+        self
+    }
+
+    pub fn with_peaks(mut self, is_peak: impl Fn(H) -> bool + 'static) -> Self {
+        self.is_peak = Box::new(is_peak);
+        self
+    }
+
+    pub fn is_trailhead(&self, height: H) -> bool {
+        (self.is_trailhead)(height)
+    }
+
+    pub fn is_peak(&self, height: H) -> bool {
+        (self.is_peak)(height)
+    }
+
+    /// Whether a step from `from` to `to` is allowed.
+    pub fn allows(&self, from: H, to: H) -> bool {
+        self.deltas.contains(&(to.into() - from.into()))
+    }
+}
+
+/// Exactly one step up at a time, in the four cardinal directions, from 0 to 9.
+impl<H: Height> Default for HikeRules<H> {
+    fn default() -> Self {
+        Self::new(1..=1, Neighborhood::Four)
+    }
+}
+
+pub struct TopographyMap<H = u8> {
+    grid: Grid<Cell<H>>,
+    rules: HikeRules<H>,
 }
 
-impl TopographyMap {
-    pub fn new(grid: Grid<u8>) -> Self {
+impl<H: Height> TopographyMap<H> {
+    pub fn new(grid: Grid<H>) -> Self {
+        Self::with_rules(grid, HikeRules::default())
+    }
+
+    pub fn with_rules(grid: Grid<H>, rules: HikeRules<H>) -> Self {
+        Self::with_impassable(
+            Grid::new(
+                grid.width(),
+                grid.height(),
+                grid.cell_iter().map(|&c| Some(c)).collect(),
+            ),
+            rules,
+        )
+    }
+
+    /// A map where the cells without a height can't be walked on.
+    pub fn with_impassable(grid: Grid<Option<H>>, rules: HikeRules<H>) -> Self {
         let cell_grid = Grid::new(
             grid.width(),
             grid.height(),
-            grid.cell_iter().map(|&c| Cell { height: c }).collect(),
+            grid.cell_iter()
+                .map(|&c| match c {
+                    Some(height) => Cell::Passable(height),
+                    None => Cell::Impassable,
+                })
+                .collect(),
         );
-        Self { grid: cell_grid }
+        Self {
+            grid: cell_grid,
+            rules,
+        }
     }
 
-    pub fn trailheads(&self) -> impl Iterator<Item = GridIndex> + use<'_> {
-        self.grid.cells_with_indices_iter().filter_map(|(i, c)| {
-            if c.is_trailhead() {
-                Some(i)
-            } else {
-                None
-            }
-        })
+    pub fn is_impassable(&self, idx: GridIndex) -> bool {
+        matches!(self.grid.get(idx), Some(Cell::Impassable))
+    }
+
+    pub fn trailheads(&self) -> impl Iterator<Item = GridIndex> + use<'_, H> {
+        self.grid.indices().filter(|&i| self.is_trailhead(i))
     }
 
+    /// The number of peaks reachable from a trailhead, searching only the cells it reaches.
+    /// [`Self::trail_counts`] is faster for scoring every trailhead.
     pub fn score(&self, trailhead: GridIndex) -> Option<u32> {
-        let Some(cell) = self.grid.get(trailhead) else {
+        if !self.is_trailhead(trailhead) {
             return None;
-        };
-        if !cell.is_trailhead() {
+        }
+
+        let reachable = self.reachable_from(trailhead);
+        Some(
+            reachable
+                .into_iter()
+                .filter(|&idx| self.is_peak(idx))
+                .count() as u32,
+        )
+    }
+
+    /// The number of distinct trails from a trailhead to any peak, searching only the cells it
+    /// reaches, or `None` if trails from it can loop. Saturates at `u64::MAX`.
+    /// [`Self::trail_counts`] is faster for rating every trailhead.
+    pub fn rating(&self, trailhead: GridIndex) -> Option<u64> {
+        if !self.is_trailhead(trailhead) {
             return None;
         }
 
-        let mut to_visit = VecDeque::with_capacity(self.grid.area());
-        to_visit.push_back(trailhead);
+        let order = self.topological_order_of(&self.reachable_from(trailhead))?;
+        let mut ratings: Grid<u64> = Grid::new_default(self.grid.width(), self.grid.height());
+        for &current in order.iter().rev() {
+            ratings[current] = match self.is_peak(current) {
+                true => 1,
+                false => self
+                    .steps_from(current)
+                    .fold(0, |rating, next| rating.saturating_add(ratings[next])),
+            };
+        }
 
-        let mut visited = HashSet::new();
-        let mut score = 0;
-        while let Some(current) = to_visit.pop_front() {
-            if visited.contains(&current) {
-                continue;
+        Some(ratings[trailhead])
+    }
+
+    /// Every trail from a trailhead to a peak, found as they are needed. There are none if
+    /// `trailhead` isn't one, and no trail visits a cell twice.
+    pub fn trails(&self, trailhead: GridIndex) -> Trails<'_, H> {
+        let stack = match self.is_trailhead(trailhead) {
+            true => vec![vec![trailhead]],
+            false => Vec::new(),
+        };
+
+        Trails {
+            map: self,
+            stack,
+            allowed: None,
+        }
+    }
+
+    /// The trails from `trailhead` that end at `peak`.
+    pub fn trails_between(&self, trailhead: GridIndex, peak: GridIndex) -> Trails<'_, H> {
+        let mut trails = self.trails(trailhead);
+        if !self.is_peak(peak) {
+            trails.stack.clear();
+            return trails;
+        }
+
+        // walk back from the peak so trails never wander off to where it can't be reached
+        let mut allowed = Grid::new_default(self.grid.width(), self.grid.height());
+        allowed[peak] = true;
+        let mut to_visit = vec![peak];
+        while let Some(current) = to_visit.pop() {
+            for neighbor_idx in self.neighbors(current) {
+                if !allowed[neighbor_idx] && self.can_step(neighbor_idx, current) {
+                    allowed[neighbor_idx] = true;
+                    to_visit.push(neighbor_idx);
+                }
             }
+        }
+        trails.allowed = Some(allowed);
+
+        trails
+    }
 
-            visited.insert(current);
+    /// Draws the map with only the heights along `trail`, and `.` everywhere else. Heights
+    /// that aren't a single digit are drawn as `#`.
+    pub fn render_trail(&self, trail: &[GridIndex]) -> String {
+        let mut on_trail = Grid::new_default(self.grid.width(), self.grid.height());
+        for &idx in trail.iter().filter(|&&idx| self.grid.contains_index(idx)) {
+            on_trail[idx] = true;
+        }
+
+        self.grid
+            .rows()
+            .map(|row| {
+                self.grid
+                    .columns()
+                    .map(|column| {
+                        let idx = GridIndex::new(column, row);
+                        match on_trail[idx] {
+                            true => self.grid[idx]
+                                .height()
+                                .and_then(|height| u32::try_from(height.into()).ok())
+                                .and_then(|height| char::from_digit(height, 10))
+                                .unwrap_or('#'),
+                            false => '.',
+                        }
+                    })
+                    .collect::<String>()
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
 
-            let current_cell = self.grid[current];
-            if current_cell.is_peak() {
-                score += 1;
-                continue;
+    /// Counts the trails and reachable peaks from every cell at once.
+    ///
+    /// Visiting the cells so that every step leads to one that is already done means each
+    /// cell's trails are the sum of theirs, and its peaks the union of theirs, kept as one bit
+    /// per peak. If the rules let trails loop there is no such order: the peaks are then found
+    /// by repeating the unions until nothing changes, and there are no ratings.
+    pub fn trail_counts(&self) -> TrailCounts {
+        let peaks: Vec<GridIndex> = self.grid.indices().filter(|&i| self.is_peak(i)).collect();
+        let words = peaks.len().div_ceil(64);
+        let order = self.topological_order();
+        let mut counts = TrailCounts {
+            ratings: Grid::new_default(self.grid.width(), self.grid.height()),
+            has_ratings: order.is_some(),
+            peaks,
+            reachable: vec![0; self.grid.area() * words],
+            words,
+        };
+
+        for (peak_number, &peak) in counts.peaks.iter().enumerate() {
+            counts.ratings[peak] = 1;
+            let offset = counts.offset(peak);
+            counts.reachable[offset + peak_number / 64] |= 1 << (peak_number % 64);
+        }
+
+        let Some(order) = order else {
+            let mut changed = true;
+            while changed {
+                changed = false;
+                for current in self.grid.indices() {
+                    for next in self.steps_from(current) {
+                        changed |= counts.merge_peaks(current, next);
+                    }
+                }
+            }
+
+            return counts;
+        };
+
+        for &current in order.iter().rev() {
+            for next in self.steps_from(current) {
+                counts.ratings[current] =
+                    counts.ratings[current].saturating_add(counts.ratings[next]);
+                counts.merge_peaks(current, next);
             }
+        }
 
-            for neighbor_idx in self.grid.cardinal_neighbor_indices_of(current) {
-                let neighbor = self.grid[neighbor_idx];
-                if !visited.contains(&neighbor_idx) && neighbor.is_reachable_from(current_cell) {
-                    to_visit.push_back(neighbor_idx);
+        counts
+    }
+
+    /// Which trailheads reach which peaks, and how many trails pass through each cell.
+    pub fn network(&self) -> TrailNetwork {
+        let counts = self.trail_counts();
+        let peaks = counts.peaks.clone();
+        let peak_numbers: HashMap<GridIndex, usize> = peaks
+            .iter()
+            .enumerate()
+            .map(|(n, &peak)| (peak, n))
+            .collect();
+        let trailheads: Vec<GridIndex> = self.trailheads().collect();
+        let reaches = trailheads
+            .iter()
+            .map(|&th| {
+                counts
+                    .reachable_peaks(th)
+                    .map(|peak| peak_numbers[&peak])
+                    .collect()
+            })
+            .collect();
+
+        // trails through a cell = trails that lead to it * trails that go on from it
+        let trails_through = self.topological_order().map(|order| {
+            let mut leading_to: Grid<u64> =
+                Grid::new_default(self.grid.width(), self.grid.height());
+            for &current in &order {
+                if self.is_trailhead(current) {
+                    leading_to[current] = leading_to[current].saturating_add(1);
+                }
+                for next in self.steps_from(current) {
+                    leading_to[next] = leading_to[next].saturating_add(leading_to[current]);
                 }
             }
+
+            let through = self
+                .grid
+                .indices()
+                .map(|idx| leading_to[idx].saturating_mul(counts.ratings[idx]))
+                .collect();
+            Grid::new(self.grid.width(), self.grid.height(), through)
+        });
+
+        TrailNetwork {
+            trailheads,
+            peaks,
+            reaches,
+            trails_through,
         }
+    }
 
-        Some(score)
+    /// The cells ordered so that every step goes forwards, or `None` if trails can loop.
+    fn topological_order(&self) -> Option<Vec<GridIndex>> {
+        self.topological_order_of(&self.grid.indices().collect::<Vec<_>>())
     }
 
-    pub fn rating(&self, trailhead: GridIndex) -> Option<u32> {
-        let Some(cell) = self.grid.get(trailhead) else {
-            return None;
+    /// Like [`Self::topological_order`], for `cells` that every step from them stays within.
+    fn topological_order_of(&self, cells: &[GridIndex]) -> Option<Vec<GridIndex>> {
+        let mut incoming: Grid<usize> = Grid::new_default(self.grid.width(), self.grid.height());
+        for &current in cells {
+            for next in self.steps_from(current) {
+                incoming[next] += 1;
+            }
+        }
+
+        let mut to_visit: VecDeque<GridIndex> = cells
+            .iter()
+            .copied()
+            .filter(|&i| incoming[i] == 0)
+            .collect();
+        let mut order = Vec::with_capacity(cells.len());
+        while let Some(current) = to_visit.pop_front() {
+            order.push(current);
+            for next in self.steps_from(current) {
+                incoming[next] -= 1;
+                if incoming[next] == 0 {
+                    to_visit.push_back(next);
+                }
+            }
+        }
+
+        (order.len() == cells.len()).then_some(order)
+    }
+
+    /// Every cell a trail from `start` can get to, `start` included.
+    fn reachable_from(&self, start: GridIndex) -> Vec<GridIndex> {
+        let mut visited = Grid::new_default(self.grid.width(), self.grid.height());
+        visited[start] = true;
+        let mut reachable = vec![start];
+        let mut to_visit = vec![start];
+        while let Some(current) = to_visit.pop() {
+            for next in self.steps_from(current) {
+                if !visited[next] {
+                    visited[next] = true;
+                    reachable.push(next);
+                    to_visit.push(next);
+                }
+            }
+        }
+
+        reachable
+    }
+
+    fn is_trailhead(&self, idx: GridIndex) -> bool {
+        self.height(idx)
+            .is_some_and(|height| self.rules.is_trailhead(height))
+    }
+
+    fn is_peak(&self, idx: GridIndex) -> bool {
+        self.height(idx)
+            .is_some_and(|height| self.rules.is_peak(height))
+    }
+
+    fn neighbors(&self, idx: GridIndex) -> Vec<GridIndex> {
+        match self.rules.neighborhood {
+            Neighborhood::Four => self.grid.cardinal_neighbor_indices_of(idx).collect(),
+            Neighborhood::Eight => self.grid.neighbor_indices_of(idx).collect(),
+        }
+    }
+
+    fn height(&self, idx: GridIndex) -> Option<H> {
+        self.grid.get(idx).and_then(|cell| cell.height())
+    }
+
+    /// Whether a trail can go from `from` to its neighbor `to`. Trails end at the first peak.
+    fn can_step(&self, from: GridIndex, to: GridIndex) -> bool {
+        match (self.height(from), self.height(to)) {
+            (Some(from_height), Some(to_height)) => {
+                !self.rules.is_peak(from_height) && self.rules.allows(from_height, to_height)
+            }
+            _ => false,
+        }
+    }
+
+    fn steps_from(&self, idx: GridIndex) -> impl Iterator<Item = GridIndex> + use<'_, H> {
+        self.neighbors(idx)
+            .into_iter()
+            .filter(move |&next| self.can_step(idx, next))
+    }
+}
+
+/// The trails from a trailhead, see [`TopographyMap::trails`].
+pub struct Trails<'a, H = u8> {
+    map: &'a TopographyMap<H>,
+    /// Partial trails still to be extended, depth first.
+    stack: Vec<Vec<GridIndex>>,
+    /// The cells the trails may pass through, if they have to end at one peak.
+    allowed: Option<Grid<bool>>,
+}
+
+impl<H: Height> Iterator for Trails<'_, H> {
+    type Item = Vec<GridIndex>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some(trail) = self.stack.pop() {
+            let current = *trail.last()?;
+            if self.map.is_peak(current) {
+                return Some(trail);
+            }
+
+            let neighbors: Vec<GridIndex> = self
+                .map
+                .steps_from(current)
+                .filter(|&n| self.allowed.as_ref().is_none_or(|allowed| allowed[n]))
+                .filter(|n| !trail.contains(n))
+                .collect();
+            // pushed in reverse so the trails come out in neighbor order
+            for neighbor_idx in neighbors.into_iter().rev() {
+                let mut extended = trail.clone();
+                extended.push(neighbor_idx);
+                self.stack.push(extended);
+            }
+        }
+
+        None
+    }
+}
+
+/// The trailheads and peaks of a [`TopographyMap`] as a bipartite graph, see
+/// [`TopographyMap::network`].
+pub struct TrailNetwork {
+    trailheads: Vec<GridIndex>,
+    peaks: Vec<GridIndex>,
+    /// For each trailhead, the positions in `peaks` of those it reaches.
+    reaches: Vec<Vec<usize>>,
+    /// `None` if trails can loop.
+    trails_through: Option<Grid<u64>>,
+}
+
+impl TrailNetwork {
+    pub fn trailheads(&self) -> &[GridIndex] {
+        &self.trailheads
+    }
+
+    pub fn peaks(&self) -> &[GridIndex] {
+        &self.peaks
+    }
+
+    /// The peaks a trailhead reaches, in reading order.
+    pub fn peaks_of(&self, trailhead: GridIndex) -> Vec<GridIndex> {
+        self.trailheads
+            .iter()
+            .position(|&th| th == trailhead)
+            .map_or_else(Vec::new, |th| {
+                self.reaches[th].iter().map(|&p| self.peaks[p]).collect()
+            })
+    }
+
+    /// The trailheads that reach a peak, in reading order.
+    pub fn trailheads_of(&self, peak: GridIndex) -> Vec<GridIndex> {
+        let Some(peak_number) = self.peaks.iter().position(|&p| p == peak) else {
+            return Vec::new();
         };
-        if !cell.is_trailhead() {
-            return None;
+
+        self.trailheads
+            .iter()
+            .zip(&self.reaches)
+            .filter(|(_, reached)| reached.contains(&peak_number))
+            .map(|(&th, _)| th)
+            .collect()
+    }
+
+    /// The trailhead that reaches the most peaks and its score, the first one on ties.
+    pub fn best_trailhead(&self) -> Option<(GridIndex, u32)> {
+        self.trailheads
+            .iter()
+            .zip(&self.reaches)
+            .map(|(&th, reached)| (th, reached.len() as u32))
+            .rev()
+            .max_by_key(|&(_, score)| score)
+    }
+
+    /// The number of trails that pass through a cell, or `None` if trails can loop. Saturates
+    /// at `u64::MAX`.
+    pub fn trails_through(&self, idx: GridIndex) -> Option<u64> {
+        self.trails_through.as_ref()?.get(idx).copied()
+    }
+
+    /// The `n` cells that lie on the most trails, with how many, most first.
+    pub fn bottlenecks(&self, n: usize) -> Option<Vec<(GridIndex, u64)>> {
+        let trails_through = self.trails_through.as_ref()?;
+        let mut cells: Vec<(GridIndex, u64)> = trails_through
+            .cells_with_indices_iter()
+            .filter(|(_, &trails)| trails > 0)
+            .map(|(idx, &trails)| (idx, trails))
+            .collect();
+        cells.sort_by_key(|&(_, trails)| std::cmp::Reverse(trails));
+        cells.truncate(n);
+
+        Some(cells)
+    }
+
+    /// The graph in Graphviz DOT format, with an edge from each trailhead to every peak it
+    /// reaches.
+    pub fn to_dot(&self) -> String {
+        let node = |kind: &str, idx: GridIndex| format!("{}_{}_{}", kind, idx.column(), idx.row());
+
+        let mut dot = String::from("digraph trails {\n    rankdir=LR;\n");
+        for &th in &self.trailheads {
+            dot += &format!(
+                "    {} [label=\"({}, {})\"];\n",
+                node("trailhead", th),
+                th.column(),
+                th.row()
+            );
         }
+        for &peak in &self.peaks {
+            dot += &format!(
+                "    {} [label=\"({}, {})\", shape=box];\n",
+                node("peak", peak),
+                peak.column(),
+                peak.row()
+            );
+        }
+        for (&th, reached) in self.trailheads.iter().zip(&self.reaches) {
+            for &p in reached {
+                dot += &format!(
+                    "    {} -> {};\n",
+                    node("trailhead", th),
+                    node("peak", self.peaks[p])
+                );
+            }
+        }
+        dot += "}\n";
 
-        let mut to_visit = VecDeque::with_capacity(self.grid.area());
-        to_visit.push_back(trailhead);
+        dot
+    }
+}
 
-        let mut rating = 0;
-        while let Some(current) = to_visit.pop_front() {
-            let current_cell = self.grid[current];
-            if current_cell.is_peak() {
-                rating += 1;
-                continue;
+/// Trail counts for every cell of a [`TopographyMap`], see [`TopographyMap::trail_counts`].
+pub struct TrailCounts {
+    ratings: Grid<u64>,
+    has_ratings: bool,
+    peaks: Vec<GridIndex>,
+    /// `words` bits per cell, one for each of `peaks`.
+    reachable: Vec<u64>,
+    words: usize,
+}
+
+impl TrailCounts {
+    /// The number of peaks reachable from `idx`.
+    pub fn score(&self, idx: GridIndex) -> Option<u32> {
+        self.ratings.get(idx)?;
+        let offset = self.offset(idx);
+
+        Some(
+            self.reachable[offset..offset + self.words]
+                .iter()
+                .map(|word| word.count_ones())
+                .sum(),
+        )
+    }
+
+    /// The number of distinct trails from `idx` to any peak, or `None` if trails can loop.
+    /// Saturates at `u64::MAX`.
+    pub fn rating(&self, idx: GridIndex) -> Option<u64> {
+        self.ratings.get(idx).copied().filter(|_| self.has_ratings)
+    }
+
+    /// The peaks reachable from `idx`, in reading order.
+    pub fn reachable_peaks(&self, idx: GridIndex) -> impl Iterator<Item = GridIndex> + '_ {
+        let words: &[u64] = if self.ratings.contains_index(idx) {
+            let offset = self.offset(idx);
+            &self.reachable[offset..offset + self.words]
+        } else {
+            &[]
+        };
+
+        self.peaks
+            .iter()
+            .enumerate()
+            .filter(move |(peak_number, _)| {
+                words
+                    .get(peak_number / 64)
+                    .is_some_and(|word| word & (1 << (peak_number % 64)) != 0)
+            })
+            .map(|(_, &peak)| peak)
+    }
+
+    /// Adds the peaks reachable from `from` to those of `to`, returning whether any were new.
+    fn merge_peaks(&mut self, to: GridIndex, from: GridIndex) -> bool {
+        let (to, from) = (self.offset(to), self.offset(from));
+        let mut changed = false;
+        for word in 0..self.words {
+            let merged = self.reachable[to + word] | self.reachable[from + word];
+            changed |= merged != self.reachable[to + word];
+            self.reachable[to + word] = merged;
+        }
+
+        changed
+    }
+
+    fn offset(&self, idx: GridIndex) -> usize {
+        (idx.row() * self.ratings.width() + idx.column()) * self.words
+    }
+}
+
+/// Parses a map of digit heights, with `.` for impassable cells.
+impl<H: Height + From<u8>> FromStr for TopographyMap<H> {
+    type Err = ParseMapError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let lines: Vec<&str> = s.lines().collect();
+        let width = lines.first().map_or(0, |line| line.chars().count());
+        if width == 0 {
+            return Err(ParseMapError::Empty);
+        }
+
+        let mut heights = Vec::with_capacity(width * lines.len());
+        let mut invalid = Vec::new();
+        for (row, line) in lines.iter().enumerate() {
+            if line.chars().count() != width {
+                return Err(ParseMapError::UnevenRow(row));
             }
 
-            for neighbor_idx in self.grid.cardinal_neighbor_indices_of(current) {
-                let neighbor = self.grid[neighbor_idx];
-                if neighbor.is_reachable_from(current_cell) {
-                    to_visit.push_back(neighbor_idx);
+            for (column, c) in line.chars().enumerate() {
+                match c {
+                    '.' => heights.push(None),
+                    _ => match c.to_digit(10) {
+                        Some(digit) => heights.push(Some(H::from(digit as u8))),
+                        None => invalid.push((GridIndex::new(column, row), c)),
+                    },
                 }
             }
         }
+        if !invalid.is_empty() {
+            return Err(ParseMapError::InvalidCharacters(invalid));
+        }
+
+        Ok(Self::with_impassable(
+            Grid::new(width, lines.len(), heights),
+            HikeRules::default(),
+        ))
+    }
+}
 
-        Some(rating)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseMapError {
+    Empty,
+    /// The row isn't as wide as the first one.
+    UnevenRow(usize),
+    /// Every character that is neither a digit nor `.`, with where it is.
+    InvalidCharacters(Vec<(GridIndex, char)>),
+}
+
+impl Display for ParseMapError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ParseMapError::Empty => write!(f, "the map is empty"),
+            ParseMapError::UnevenRow(row) => write!(f, "row {} has a different width", row),
+            ParseMapError::InvalidCharacters(invalid) => {
+                write!(f, "invalid characters:")?;
+                for (idx, c) in invalid {
+                    write!(f, " {:?} at row {}, column {};", c, idx.row(), idx.column())?;
+                }
+                Ok(())
+            }
+        }
     }
 }
 
+impl Error for ParseMapError {}
+
 #[derive(Clone, Copy, PartialEq, Eq, Debug)]
-struct Cell {
-    height: u8,
+enum Cell<H> {
+    Passable(H),
+    Impassable,
+}
+
+impl<H: Copy> Cell<H> {
+    fn height(&self) -> Option<H> {
+        match self {
+            Cell::Passable(height) => Some(*height),
+            Cell::Impassable => None,
+        }
+    }
 }
 
-impl Cell {
-    pub fn is_trailhead(&self) -> bool {
-        self.height == 0
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXAMPLE: [&str; 8] = [
+        "89010123", "78121874", "87430965", "96549874", "45678903", "32019012", "01329801",
+        "10456732",
+    ];
+
+    fn heights(rows: &[&str]) -> Grid<u8> {
+        let heights = rows
+            .iter()
+            .flat_map(|row| row.bytes().map(|b| b - b'0'))
+            .collect();
+        Grid::new(rows[0].len(), rows.len(), heights)
+    }
+
+    fn topography_map(rows: &[&str]) -> TopographyMap {
+        TopographyMap::new(heights(rows))
+    }
+
+    #[test]
+    fn trail_counts_test() {
+        let map = topography_map(&EXAMPLE);
+        let counts = map.trail_counts();
+
+        let scores: Vec<u32> = map
+            .trailheads()
+            .map(|th| counts.score(th).unwrap())
+            .collect();
+        assert_eq!(scores, [5, 6, 5, 3, 1, 3, 5, 3, 5]);
+        let ratings: Vec<u64> = map
+            .trailheads()
+            .map(|th| counts.rating(th).unwrap())
+            .collect();
+        assert_eq!(ratings, [20, 24, 10, 4, 1, 4, 5, 8, 5]);
+
+        let th = GridIndex::new(2, 0);
+        assert_eq!(map.score(th), Some(5));
+        assert_eq!(map.rating(th), Some(20));
+        assert_eq!(counts.reachable_peaks(th).count(), 5);
+        assert_eq!(map.score(GridIndex::new(0, 0)), None);
+        assert_eq!(counts.score(GridIndex::new(8, 0)), None);
+    }
+
+    #[test]
+    fn trails_test() {
+        let map = topography_map(&EXAMPLE);
+        for th in map.trailheads() {
+            let trails: Vec<_> = map.trails(th).collect();
+            assert_eq!(trails.len() as u64, map.rating(th).unwrap());
+            assert!(trails
+                .iter()
+                .all(|trail| trail.len() == 10 && trail[0] == th));
+        }
+        assert_eq!(map.trails(GridIndex::new(0, 0)).count(), 0);
+
+        let th = GridIndex::new(2, 0);
+        let peak = GridIndex::new(1, 0);
+        let trails: Vec<_> = map.trails_between(th, peak).collect();
+        assert!(trails.iter().all(|trail| trail.last() == Some(&peak)));
+        assert_eq!(
+            trails.len(),
+            map.trails(th)
+                .filter(|trail| trail.last() == Some(&peak))
+                .count()
+        );
+        assert_eq!(map.trails_between(th, th).count(), 0);
+
+        let map = topography_map(&["0123", "1234", "8765", "9876"]);
+        let trail = map.trails(GridIndex::new(0, 0)).next().unwrap();
+        assert_eq!(map.render_trail(&trail), "0123\n...4\n...5\n9876");
     }
 
-    pub fn is_peak(&self) -> bool {
-        self.height == 9
+    #[test]
+    fn rules_test() {
+        let total_rating = |map: &TopographyMap| -> u64 {
+            let counts = map.trail_counts();
+            map.trailheads().map(|th| counts.rating(th).unwrap()).sum()
+        };
+
+        // walking the trails backwards finds the same ones
+        let descending = TopographyMap::with_rules(
+            heights(&EXAMPLE),
+            HikeRules::new(-1..=-1, Neighborhood::Four)
+                .with_trailheads(|height| height == 9)
+                .with_peaks(|height| height == 0),
+        );
+        assert_eq!(total_rating(&descending), 81);
+
+        let diagonal = Grid::new(2, 2, vec![1000_u32, 5, 5, 1001]);
+        let rules = || {
+            HikeRules::new(1..=1, Neighborhood::Eight)
+                .with_trailheads(|height| height == 1000)
+                .with_peaks(|height| height > 1000)
+        };
+        let map = TopographyMap::with_rules(diagonal.clone(), rules());
+        assert_eq!(map.score(GridIndex::new(0, 0)), Some(1));
+        let map = TopographyMap::with_rules(
+            diagonal,
+            HikeRules {
+                neighborhood: Neighborhood::Four,
+                ..rules()
+            },
+        );
+        assert_eq!(map.score(GridIndex::new(0, 0)), Some(0));
+
+        // stepping back and forth could go on forever, so only the peaks are counted
+        let looping = TopographyMap::with_rules(
+            Grid::new(4, 1, vec![0_u8, 1, 2, 9]),
+            HikeRules::new(-1..=7, Neighborhood::Four),
+        );
+        let th = GridIndex::new(0, 0);
+        assert_eq!(looping.score(th), Some(1));
+        assert_eq!(looping.rating(th), None);
+        assert_eq!(looping.trails(th).count(), 1);
     }
 
-    pub fn is_reachable_from(&self, from: Self) -> bool {
-        from.height < self.height && self.height - from.height == 1
+    #[test]
+    fn parse_test() {
+        let map: TopographyMap = "..90..9\n...1.98\n...2..7\n6543456\n765.987\n876....\n987...."
+            .parse()
+            .unwrap();
+        assert_eq!(map.score(GridIndex::new(3, 0)), Some(4));
+        assert!(map.is_impassable(GridIndex::new(0, 0)));
+
+        let map: TopographyMap = "10..9..\n2...8..\n3...7..\n4567654\n...8..3\n...9..2\n.....01"
+            .parse()
+            .unwrap();
+        let scores: Vec<_> = map.trailheads().map(|th| map.score(th).unwrap()).collect();
+        assert_eq!(scores, [1, 2]);
+
+        let map: TopographyMap = ".....0.\n..4321.\n..5..2.\n..6543.\n..7..4.\n..8765.\n..9...."
+            .parse()
+            .unwrap();
+        assert_eq!(map.rating(GridIndex::new(5, 0)), Some(3));
+
+        assert_eq!(
+            "01\n2x\n#4".parse::<TopographyMap>().err(),
+            Some(ParseMapError::InvalidCharacters(vec![
+                (GridIndex::new(1, 1), 'x'),
+                (GridIndex::new(0, 2), '#')
+            ]))
+        );
+        assert_eq!(
+            "012\n34".parse::<TopographyMap<u32>>().err(),
+            Some(ParseMapError::UnevenRow(1))
+        );
+        assert_eq!(
+            "".parse::<TopographyMap>().err(),
+            Some(ParseMapError::Empty)
+        );
+    }
+
+    #[test]
+    fn network_test() {
+        let map = topography_map(&EXAMPLE);
+        let network = map.network();
+
+        let th = GridIndex::new(2, 0);
+        assert_eq!(network.peaks_of(th).len(), 5);
+        let reached: usize = network
+            .peaks()
+            .iter()
+            .map(|&peak| network.trailheads_of(peak).len())
+            .sum();
+        assert_eq!(reached, 36);
+        assert_eq!(network.best_trailhead(), Some((GridIndex::new(4, 0), 6)));
+
+        assert_eq!(network.trails_through(th), map.rating(th));
+        let bottlenecks = network.bottlenecks(3).unwrap();
+        assert_eq!(bottlenecks.len(), 3);
+        assert!(map
+            .trailheads()
+            .all(|th| network.trails_through(th) <= Some(bottlenecks[0].1)));
+
+        let map = topography_map(&["0123456789"]);
+        let dot = map.network().to_dot();
+        assert!(dot.contains("    trailhead_0_0 [label=\"(0, 0)\"];\n"));
+        assert!(dot.contains("    peak_9_0 [label=\"(9, 0)\", shape=box];\n"));
+        assert!(dot.contains("    trailhead_0_0 -> peak_9_0;\n"));
+    }
+
+    #[test]
+    fn saturation_test() {
+        // every column one higher than the last, so each step has up to three ways forward
+        let (width, height) = (60, 10);
+        let heights = (0..height).flat_map(|_| 0..width).collect();
+        let rules = HikeRules::new(1..=1, Neighborhood::Eight)
+            .with_peaks(move |height| height == width - 1);
+        let map = TopographyMap::with_rules(Grid::new(width as usize, height, heights), rules);
+
+        let th = GridIndex::new(0, 0);
+        assert_eq!(map.score(th), Some(10));
+        assert_eq!(map.rating(th), Some(u64::MAX));
+        assert_eq!(map.trail_counts().rating(th), Some(u64::MAX));
+        assert_eq!(map.network().trails_through(th), Some(u64::MAX));
+    }
+
+    #[test]
+    fn many_peaks_test() {
+        // 100 peaks take two words per cell
+        let map = TopographyMap::new(Grid::new(10, 100, (0..100).flat_map(|_| 0..10).collect()));
+        let counts = map.trail_counts();
+
+        let last_row = GridIndex::new(0, 99);
+        assert_eq!(counts.score(last_row), Some(1));
+        assert_eq!(
+            counts.reachable_peaks(last_row).collect::<Vec<_>>(),
+            [GridIndex::new(9, 99)]
+        );
     }
 }
@@ -375,7 +375,7 @@ mod day6 {
 #[cfg(test)]
 mod day7 {
     use super::*;
-    use elephants::{Equation, Operation};
+    use elephants::{calibrate, Addition, Concatenation, Equation, Multiplication};
 
     fn test_file(name: &str) -> String {
         read_file_contents(&input_data("day7", name))
@@ -407,11 +407,7 @@ mod day7 {
 
         for (expected_result, equation) in equations {
             if equation
-                .all_results(
-                    &[Operation::Addition, Operation::Multiplication],
-                    expected_result,
-                )
-                .into_iter()
+                .all_results(&[&Addition, &Multiplication], expected_result)
                 .any(|r| r == expected_result)
             {
                 total += expected_result;
@@ -422,21 +418,14 @@ mod day7 {
 
     fn solve_part2(content: &str) -> i64 {
         let equations = parse_equations_with_results(content);
-        let mut total = 0;
-        for (expected_result, equation) in equations {
-            if equation.can_result_in(
-                &[
-                    Operation::Addition,
-                    Operation::Multiplication,
-                    Operation::Concatenation,
-                ],
-                expected_result,
-            ) {
-                total += expected_result;
-            }
-        }
 
-        total
+        calibrate(
+            &equations,
+            &[&Addition, &Multiplication, &Concatenation::default()],
+            4,
+        )
+        .unwrap()
+        .total()
     }
 
     #[test]
@@ -463,7 +452,7 @@ mod day7 {
 #[cfg(test)]
 mod day8 {
     use super::*;
-    use antenna::{Antenna, AntennaMap};
+    use antenna::{Antenna, AntennaMap, AntinodeRule, Resonance};
     use simple_grid::Grid;
 
     fn test_file(name: &str) -> String {
@@ -486,16 +475,16 @@ mod day8 {
 
     fn solve_part1(content: &str) -> usize {
         let antenna_map = parse_antenna_map(content);
-        let antinodes = antenna_map.get_antinodes();
+        let antinodes = antenna_map.get_antinodes(&AntinodeRule::default());
 
-        antinodes.cell_iter().filter(|c| !c.is_empty()).count()
+        antinodes.len()
     }
 
     fn solve_part2(content: &str) -> usize {
         let antenna_map = parse_antenna_map(content);
-        let antinodes = antenna_map.get_resonant_antinodes();
+        let antinodes = antenna_map.get_resonant_antinodes(Resonance::AntennaSpacing);
 
-        antinodes.cell_iter().filter(|c| !c.is_empty()).count()
+        antinodes.len()
     }
 
     #[test]
@@ -578,42 +567,35 @@ mod day9 {
 mod day10 {
     use super::*;
     use hike::TopographyMap;
-    use simple_grid::{Grid, GridIndex};
 
     fn test_file(name: &str) -> String {
         read_file_contents(&input_data("day10", name))
     }
 
     fn parse_map(content: &str) -> TopographyMap {
-        let lines: Vec<_> = content.lines().collect();
-        let heights: Vec<u8> = lines
-            .iter()
-            .map(|l| l.chars())
-            .flatten()
-            .map(|c| c.to_digit(10).unwrap() as u8)
-            .collect();
-
-        TopographyMap::new(Grid::new(lines[0].len(), lines.len(), heights))
+        content.parse().unwrap()
     }
 
     fn solve_part1(content: &str) -> u32 {
         let map = parse_map(content);
+        let counts = map.trail_counts();
 
         let mut score = 0;
         for th in map.trailheads() {
-            let th_score = map.score(th).unwrap();
+            let th_score = counts.score(th).unwrap();
             score += th_score;
         }
 
         score
     }
 
-    fn solve_part2(content: &str) -> u32 {
+    fn solve_part2(content: &str) -> u64 {
         let map = parse_map(content);
+        let counts = map.trail_counts();
 
         let mut rating = 0;
         for th in map.trailheads() {
-            let th_rating = map.rating(th).unwrap();
+            let th_rating = counts.rating(th).unwrap();
             rating += th_rating;
         }
 
@@ -657,13 +639,13 @@ mod day11 {
     fn solve_part1(content: &str) -> u64 {
         let mut stones = parse_stones(content);
 
-        stones.blink(25)
+        stones.blink(25).unwrap()
     }
 
     fn solve_part2(content: &str) -> u64 {
         let mut stones = parse_stones(content);
 
-        stones.blink(75)
+        stones.blink(75).unwrap()
     }
 
     #[test]
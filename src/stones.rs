@@ -1,8 +1,12 @@
 use std::{
     cell::RefCell,
     collections::{HashMap, VecDeque},
+    fmt::Debug,
 };
 
+const COUNT_OVERFLOW: &str = "the number of stones overflows";
+const VALUE_OVERFLOW: &str = "a stone's value overflows u64";
+
 #[derive(Clone, PartialEq, Eq, Debug)]
 pub struct Stones {
     stones: Vec<Stone>,
@@ -19,11 +23,121 @@ impl Stones {
         }
     }
 
-    pub fn blink(&self, blinks: u32) -> u64 {
-        self.stones
-            .iter()
-            .map(|s| s.blink(blinks, &mut self.memo.borrow_mut()))
-            .sum()
+    /// The number of stones after `blinks` blinks. Use [`StoneCounts<u128>`] if it doesn't fit
+    /// in `u64`.
+    pub fn blink(&self, blinks: u32) -> Result<u64, &'static str> {
+        let mut total: u64 = 0;
+        for stone in &self.stones {
+            let stones = stone.blink(blinks, &mut self.memo.borrow_mut())?;
+            total = total.checked_add(stones).ok_or(COUNT_OVERFLOW)?;
+        }
+
+        Ok(total)
+    }
+
+    pub fn counts<C: Count>(&self) -> StoneCounts<C> {
+        let mut counts = StoneCounts::default();
+        for &stone in &self.stones {
+            counts
+                .add(stone, C::from(1))
+                .expect("there are fewer stones in a slice than fit in a count");
+        }
+
+        counts
+    }
+}
+
+/// A number of stones. `u128` holds far more blinks than `u64`.
+pub trait Count: Copy + Default + Eq + Debug + From<u8> {
+    fn checked_add(self, rhs: Self) -> Option<Self>;
+}
+
+macro_rules! impl_count {
+    ($($t:ty),*) => {
+        $(
+            impl Count for $t {
+                fn checked_add(self, rhs: Self) -> Option<Self> {
+                    <$t>::checked_add(self, rhs)
+                }
+            }
+        )*
+    };
+}
+
+impl_count!(u64, u128);
+
+/// The stones as a multiset: how many there are of each value. Order doesn't change how
+/// stones blink, so blinking one value once covers every stone with it.
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+pub struct StoneCounts<C = u64> {
+    counts: HashMap<Stone, C>,
+}
+
+impl<C: Count> StoneCounts<C> {
+    pub fn new(v: &[u64]) -> Self {
+        Stones::new(v).counts()
+    }
+
+    /// The stones after one more blink.
+    pub fn blink_once(&self) -> Result<Self, &'static str> {
+        let mut next = Self {
+            counts: HashMap::with_capacity(self.counts.len() * 2),
+        };
+        for (&stone, &count) in &self.counts {
+            match stone.blink_once()? {
+                BlinkResult::OneStone(a) => next.add(a, count)?,
+                BlinkResult::TwoStones(a, b) => {
+                    next.add(a, count)?;
+                    next.add(b, count)?;
+                }
+            }
+        }
+
+        Ok(next)
+    }
+
+    /// The stones after `blinks` more blinks.
+    pub fn after(&self, blinks: u32) -> Result<Self, &'static str> {
+        (0..blinks).try_fold(self.clone(), |counts, _| counts.blink_once())
+    }
+
+    /// The number of stones after each of `blinks` more blinks.
+    pub fn series(&self, blinks: u32) -> Result<Vec<C>, &'static str> {
+        let mut counts = self.clone();
+        (0..blinks)
+            .map(|_| {
+                counts = counts.blink_once()?;
+                counts.total()
+            })
+            .collect()
+    }
+
+    pub fn total(&self) -> Result<C, &'static str> {
+        self.counts
+            .values()
+            .try_fold(C::default(), |total, &count| total.checked_add(count))
+            .ok_or(COUNT_OVERFLOW)
+    }
+
+    /// The number of stones engraved with `value`.
+    pub fn count_of(&self, value: u64) -> C {
+        self.counts.get(&Stone(value)).copied().unwrap_or_default()
+    }
+
+    pub fn distinct(&self) -> usize {
+        self.counts.len()
+    }
+
+    /// Every value with how many stones have it, in no particular order.
+    pub fn iter(&self) -> impl Iterator<Item = (u64, C)> + '_ {
+        self.counts.iter().map(|(stone, &count)| (stone.0, count))
+    }
+
+    fn add(&mut self, stone: Stone, count: C) -> Result<(), &'static str> {
+        let entry = self.counts.entry(stone).or_default();
+        *entry = entry.checked_add(count).ok_or(COUNT_OVERFLOW)?;
+
+        Ok(())
     }
 }
 
@@ -35,33 +149,38 @@ impl Stone {
         Self(n)
     }
 
-    fn blink(self, blinks: u32, memo: &mut HashMap<(Stone, u32), u64>) -> u64 {
+    fn blink(
+        self,
+        blinks: u32,
+        memo: &mut HashMap<(Stone, u32), u64>,
+    ) -> Result<u64, &'static str> {
         if blinks == 0 {
-            1
+            Ok(1)
         } else if let Some(&stones) = memo.get(&(self, blinks)) {
-            stones
+            Ok(stones)
         } else {
-            let result = if self.0 == 0 {
-                Stone(1).blink(blinks - 1, memo)
-            } else if let Some((a, b)) = self.split() {
-                a.blink(blinks - 1, memo) + b.blink(blinks - 1, memo)
-            } else {
-                Stone(self.0 * 2024).blink(blinks - 1, memo)
+            let result = match self.blink_once()? {
+                BlinkResult::OneStone(a) => a.blink(blinks - 1, memo)?,
+                BlinkResult::TwoStones(a, b) => a
+                    .blink(blinks - 1, memo)?
+                    .checked_add(b.blink(blinks - 1, memo)?)
+                    .ok_or(COUNT_OVERFLOW)?,
             };
 
             memo.insert((self, blinks), result);
 
-            result
+            Ok(result)
         }
     }
 
-    fn blink_once(&self) -> BlinkResult {
+    fn blink_once(&self) -> Result<BlinkResult, &'static str> {
         if self.0 == 0 {
-            BlinkResult::OneStone(Stone(1))
+            Ok(BlinkResult::OneStone(Stone(1)))
         } else if let Some((a, b)) = self.split() {
-            BlinkResult::TwoStones(a, b)
+            Ok(BlinkResult::TwoStones(a, b))
         } else {
-            BlinkResult::OneStone(Stone(self.0 * 2024))
+            let engraved = self.0.checked_mul(2024).ok_or(VALUE_OVERFLOW)?;
+            Ok(BlinkResult::OneStone(Stone(engraved)))
         }
     }
 
@@ -93,7 +212,7 @@ fn split_number(n: u64) -> Option<(u64, u64)> {
 }
 
 fn number_of_digits(n: u64) -> u32 {
-    ((n as f64).log10().floor() as u32 + 1)
+    n.checked_ilog10().map_or(1, |log| log + 1)
 }
 
 fn has_even_number_of_digits(n: u64) -> bool {
@@ -125,6 +244,35 @@ mod tests {
         for n in 10000..100000 {
             assert_eq!(number_of_digits(n), 5, "{}", n);
         }
+
+        // a float log10 rounds these up to the next power of ten
+        assert_eq!(number_of_digits(999_999_999_999_999_999), 18);
+        assert_eq!(number_of_digits(9_999_999_999_999_999), 16);
+        assert_eq!(number_of_digits(u64::MAX), 20);
+    }
+
+    #[test]
+    fn overflow_test() {
+        // 20 digits splits, but 19 odd digits can't be multiplied by 2024
+        let too_large = 1_000_000_000_000_000_000;
+        assert_eq!(Stones::new(&[too_large]).blink(1), Err(VALUE_OVERFLOW));
+        assert_eq!(
+            StoneCounts::<u64>::new(&[too_large]).blink_once(),
+            Err(VALUE_OVERFLOW)
+        );
+        assert_eq!(Stones::new(&[u64::MAX]).blink(1), Ok(2));
+
+        let mut counts = StoneCounts::<u64>::default();
+        counts.add(Stone(0), u64::MAX).unwrap();
+        assert_eq!(counts.blink_once().unwrap().total(), Ok(u64::MAX));
+        counts.add(Stone(1), 1).unwrap();
+        assert_eq!(counts.total(), Err(COUNT_OVERFLOW));
+        assert_eq!(counts.blink_once().unwrap().total(), Err(COUNT_OVERFLOW));
+
+        let mut wide = StoneCounts::<u128>::default();
+        wide.add(Stone(0), u64::MAX as u128).unwrap();
+        wide.add(Stone(1), 1).unwrap();
+        assert_eq!(wide.total(), Ok(u64::MAX as u128 + 1));
     }
 
     #[test]
@@ -134,6 +282,24 @@ mod tests {
         assert_eq!(split_number(12345), None);
     }
 
+    #[test]
+    fn stone_counts_test() {
+        let counts: StoneCounts = StoneCounts::new(&[125, 17]);
+        assert_eq!(counts.series(6).unwrap(), [3, 4, 5, 9, 13, 22]);
+
+        // 2097446912 14168 4048 2 0 2 4 40 48 2024 40 48 80 96 2 8 6 7 6 0 3 2
+        let after = counts.after(6).unwrap();
+        assert_eq!(after.total(), Ok(22));
+        assert_eq!(after.count_of(0), 2);
+        assert_eq!(after.count_of(2), 4);
+        assert_eq!(after.count_of(1), 0);
+        assert_eq!(after.distinct(), 15);
+        assert_eq!(after.iter().map(|(_, count)| count).sum::<u64>(), 22);
+
+        assert_eq!(counts.after(25).unwrap().total(), Ok(55312));
+        assert_eq!(counts.after(0).unwrap(), counts);
+    }
+
     #[test]
     fn cycle_test() {
         let start = Stone(0);
@@ -142,7 +308,7 @@ mod tests {
         for _ in 0..100 {
             println!("{}", current.0);
 
-            let next = current.blink_once();
+            let next = current.blink_once().unwrap();
             match next {
                 BlinkResult::OneStone(stone) => current = stone,
                 BlinkResult::TwoStones(a, _b) => current = a,